@@ -0,0 +1,80 @@
+use std::path::Path;
+use std::sync::Mutex;
+
+use indicatif::{MultiProgress, ProgressBar, ProgressStyle};
+use launcher_java_installer::{JavaSetup, ProgressReporter, Stage};
+
+// Draws one progress bar per setup stage
+struct IndicatifReporter {
+    bars: MultiProgress,
+    current: Mutex<ProgressBar>,
+}
+
+impl IndicatifReporter {
+    fn new() -> Self {
+        IndicatifReporter {
+            bars: MultiProgress::new(),
+            current: Mutex::new(ProgressBar::hidden()),
+        }
+    }
+
+    fn bar(&self) -> ProgressBar {
+        self.current.lock().unwrap().clone()
+    }
+}
+
+impl ProgressReporter for IndicatifReporter {
+    fn stage_started(&self, stage: Stage) {
+        let bar = self.bars.add(ProgressBar::no_length());
+        bar.set_style(ProgressStyle::with_template("{spinner} {msg:10} {pos}").unwrap());
+        bar.set_message(format!("{:?}", stage));
+        *self.current.lock().unwrap() = bar;
+    }
+
+    fn bytes_progress(&self, downloaded: u64, total: Option<u64>) {
+        let bar = self.bar();
+        if let Some(total) = total
+            && bar.length() != Some(total)
+        {
+            bar.set_style(
+                ProgressStyle::with_template("{msg:10} [{bar:40}] {bytes}/{total_bytes} ({bytes_per_sec})").unwrap(),
+            );
+            bar.set_length(total);
+        }
+        bar.set_position(downloaded);
+    }
+
+    fn entry_progress(&self, extracted: u64, total: Option<u64>) {
+        let bar = self.bar();
+        if let Some(total) = total
+            && bar.length() != Some(total)
+        {
+            bar.set_style(ProgressStyle::with_template("{msg:10} [{bar:40}] {pos}/{len} entries").unwrap());
+            bar.set_length(total);
+        }
+        bar.set_position(extracted);
+    }
+
+    fn file_copied(&self, _path: &Path) {
+        self.bar().inc(1);
+    }
+
+    fn stage_finished(&self, stage: Stage) {
+        self.bar().finish_with_message(format!("{:?} done", stage));
+    }
+}
+
+#[tokio::main]
+async fn main() {
+    let temp = std::env::temp_dir().join("indicatif-example");
+    let path = |name: &str| temp.join(name).to_string_lossy().into_owned();
+
+    let mut setup = JavaSetup::new("17", &path("download/"), &path("extract"), &path("jdk-17"));
+    setup.set_progress_reporter(IndicatifReporter::new());
+    // Leaves PATH alone, the example only shows the progress
+    setup.set_launcher_shim_dir(Some(temp.join("bin")));
+    match setup.setup().await {
+        Ok(report) => println!("Installed to {}", report.install_path.display()),
+        Err(e) => eprintln!("Setup failed: {}", e),
+    }
+}
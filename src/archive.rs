@@ -0,0 +1,321 @@
+use std::collections::HashSet;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use log::warn;
+use tokio_util::sync::CancellationToken;
+
+use crate::progress::ProgressReporter;
+use crate::JavaSetupError;
+
+// Readers archive handlers can consume, zip needs to seek through its central directory
+pub trait ArchiveReader: Read + Seek {}
+
+impl<T: Read + Seek> ArchiveReader for T {}
+
+// Unpacks one archive format into a destination directory
+pub trait ArchiveExtractor: Send + Sync {
+    // The setup passes its duplicate entry policy and progress reporter, handlers may ignore either
+    fn extract(
+        &self,
+        reader: &mut dyn ArchiveReader,
+        destination: &Path,
+        duplicates: DuplicateEntryPolicy,
+        progress: &dyn ProgressReporter,
+    ) -> Result<(), Box<dyn std::error::Error + Send + Sync>>;
+}
+
+// Fails every read once cancel is cancelled. Handlers read the archive for each entry they extract,
+// so wrapping their reader stops built-in and custom handlers alike without a change to the trait
+pub(crate) struct CancellableReader<R> {
+    inner: R,
+    cancel: CancellationToken,
+}
+
+impl<R> CancellableReader<R> {
+    pub(crate) fn new(inner: R, cancel: CancellationToken) -> Self {
+        CancellableReader { inner, cancel }
+    }
+}
+
+impl<R: Read> Read for CancellableReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        if self.cancel.is_cancelled() {
+            // Not Interrupted, which read_exact and io::copy retry
+            return Err(std::io::Error::other(JavaSetupError::Cancelled));
+        }
+        self.inner.read(buf)
+    }
+}
+
+impl<R: Seek> Seek for CancellableReader<R> {
+    fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+        self.inner.seek(pos)
+    }
+}
+
+// What to do with a file entry whose name already appeared earlier in the archive
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DuplicateEntryPolicy {
+    // Fails the extraction with DuplicateEntry
+    Error,
+    KeepFirst,
+    // Later entries overwrite earlier ones
+    #[default]
+    KeepLast,
+}
+
+// Remembers the files extracted so far to apply the duplicate entry policy
+pub(crate) struct DuplicateTracker {
+    policy: DuplicateEntryPolicy,
+    seen: HashSet<PathBuf>,
+}
+
+impl DuplicateTracker {
+    pub(crate) fn new(policy: DuplicateEntryPolicy) -> Self {
+        DuplicateTracker { policy, seen: HashSet::new() }
+    }
+
+    // Whether the file entry should be extracted
+    fn admit(&mut self, name: &Path) -> Result<bool, JavaSetupError> {
+        // Compares normalized paths so "a/./b" and "a/b" count as the same file
+        let normalized: PathBuf = name.components().collect();
+        if self.seen.insert(normalized) {
+            return Ok(true);
+        }
+        match self.policy {
+            DuplicateEntryPolicy::Error => Err(JavaSetupError::DuplicateEntry(name.display().to_string())),
+            DuplicateEntryPolicy::KeepFirst => {
+                warn!("Skipping duplicate archive entry {}", name.display());
+                Ok(false)
+            }
+            DuplicateEntryPolicy::KeepLast => {
+                warn!("Duplicate archive entry {} overwrites the earlier one", name.display());
+                Ok(true)
+            }
+        }
+    }
+}
+
+// Built-in handler for .zip archives (Windows JDK packages). ZipArchive keeps only the last entry
+// of a name spelled exactly the same, the policy applies to the entries it lists
+pub struct ZipExtractor;
+
+impl ArchiveExtractor for ZipExtractor {
+    fn extract(
+        &self,
+        reader: &mut dyn ArchiveReader,
+        destination: &Path,
+        duplicates: DuplicateEntryPolicy,
+        progress: &dyn ProgressReporter,
+    ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let mut archive = zip::ZipArchive::new(reader)?;
+        let mut duplicates = DuplicateTracker::new(duplicates);
+
+        // Iterates through the archive and extracts files
+        let total = archive.len() as u64;
+        for i in 0..archive.len() {
+            extract_zip_entry(&mut archive, i, destination, &mut duplicates)?;
+            progress.entry_progress(i as u64 + 1, Some(total));
+        }
+        Ok(())
+    }
+}
+
+// Extracts the i-th entry of a zip archive, also used by Extractor::extract_async between yields
+pub(crate) fn extract_zip_entry<R: Read + Seek>(
+    archive: &mut zip::ZipArchive<R>,
+    i: usize,
+    destination: &Path,
+    duplicates: &mut DuplicateTracker,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let mut file = archive.by_index(i)?;
+    // enclosed_name is None for absolute names and for names whose .. components climb above the
+    // root, the archive is refused rather than writing outside destination
+    let name = file
+        .enclosed_name()
+        .ok_or_else(|| JavaSetupError::UnsafeArchiveEntry(file.name().to_string()))?;
+    let outpath = destination.join(&name);
+
+    if (*file.name()).ends_with('/') {
+        std::fs::create_dir_all(&outpath)?;
+    } else if duplicates.admit(&name)? {
+        if let Some(p) = outpath.parent()
+            && !p.exists()
+        {
+            std::fs::create_dir_all(p)?;
+        }
+        let mut outfile = std::fs::File::create(&outpath)?;
+        std::io::copy(&mut file, &mut outfile)?;
+    }
+    Ok(())
+}
+
+// Built-in handler for .tar.gz archives (Linux and macOS JDK packages)
+pub struct TarGzExtractor;
+
+impl ArchiveExtractor for TarGzExtractor {
+    fn extract(
+        &self,
+        reader: &mut dyn ArchiveReader,
+        destination: &Path,
+        duplicates: DuplicateEntryPolicy,
+        progress: &dyn ProgressReporter,
+    ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        unpack_tar_gz(reader, destination, duplicates, progress)
+    }
+}
+
+// tar.gz only needs a forward reader, so it can also unpack straight from a download stream
+pub(crate) fn unpack_tar_gz<R: Read>(
+    reader: R,
+    destination: &Path,
+    duplicates: DuplicateEntryPolicy,
+    progress: &dyn ProgressReporter,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let decoder = flate2::read::GzDecoder::new(reader);
+    let mut archive = tar::Archive::new(decoder);
+    let mut duplicates = DuplicateTracker::new(duplicates);
+    std::fs::create_dir_all(destination)?;
+
+    for (i, entry) in archive.entries()?.enumerate() {
+        let mut entry = entry?;
+        if entry.header().entry_type().is_dir() || duplicates.admit(&entry.path()?)? {
+            // unpack_in refuses entries that would land outside destination
+            entry.unpack_in(destination)?;
+        }
+        progress.entry_progress(i as u64 + 1, None);
+    }
+    Ok(())
+}
+
+// Maps file extensions to the handler able to extract them
+#[derive(Clone)]
+pub struct ExtractorRegistry {
+    handlers: Vec<Handler>,
+}
+
+#[derive(Clone)]
+struct Handler {
+    extension: String,
+    extractor: Arc<dyn ArchiveExtractor>,
+    // Registered by Default rather than by the caller
+    builtin: bool,
+}
+
+impl Default for ExtractorRegistry {
+    fn default() -> Self {
+        let mut registry = ExtractorRegistry { handlers: Vec::new() };
+        registry.register("zip", ZipExtractor);
+        registry.register("tar.gz", TarGzExtractor);
+        registry.register("tgz", TarGzExtractor);
+        for handler in &mut registry.handlers {
+            handler.builtin = true;
+        }
+        registry
+    }
+}
+
+impl ExtractorRegistry {
+    // Registering an extension again replaces its handler
+    pub fn register<E>(&mut self, extension: &str, extractor: E)
+    where
+        E: ArchiveExtractor + 'static,
+    {
+        let extension = extension.trim_start_matches('.').to_ascii_lowercase();
+        self.handlers.retain(|handler| handler.extension != extension);
+        self.handlers.push(Handler { extension, extractor: Arc::new(extractor), builtin: false });
+    }
+
+    // Finds the handler for a file name, the longest matching extension wins (tar.gz over gz)
+    pub fn find(&self, file_name: &str) -> Option<Arc<dyn ArchiveExtractor>> {
+        self.find_handler(file_name).map(|handler| handler.extractor.clone())
+    }
+
+    // Whether the file is handled by the built-in zip extractor (no custom zip handler registered)
+    pub(crate) fn is_builtin_zip(&self, file_name: &str) -> bool {
+        self.find_handler(file_name)
+            .is_some_and(|handler| handler.builtin && handler.extension == "zip")
+    }
+
+    fn find_handler(&self, file_name: &str) -> Option<&Handler> {
+        let file_name = file_name.to_ascii_lowercase();
+        self.handlers
+            .iter()
+            .filter(|handler| file_name.ends_with(&format!(".{}", handler.extension)))
+            .max_by_key(|handler| handler.extension.len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::progress::NoopReporter;
+    use crate::test_util::TempDir;
+    use std::io::Write;
+
+    // Builds a zip holding the given files, in order
+    fn zip_with(entries: &[(&str, &str)]) -> std::io::Cursor<Vec<u8>> {
+        let mut writer = zip::ZipWriter::new(std::io::Cursor::new(Vec::new()));
+        for (name, content) in entries {
+            writer.start_file(*name, zip::write::SimpleFileOptions::default()).unwrap();
+            writer.write_all(content.as_bytes()).unwrap();
+        }
+        let mut archive = writer.finish().unwrap();
+        archive.set_position(0);
+        archive
+    }
+
+    fn extract_zip(
+        entries: &[(&str, &str)],
+        destination: &Path,
+        duplicates: DuplicateEntryPolicy,
+    ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        ZipExtractor.extract(&mut zip_with(entries), destination, duplicates, &NoopReporter)
+    }
+
+    // Two spellings of the same file, ZipArchive lists both
+    const DUPLICATED: &[(&str, &str)] = &[("jdk/bin/java", "first"), ("jdk/./bin/java", "second")];
+
+    #[test]
+    fn zip_duplicates_follow_the_policy() {
+        let dir = TempDir::new("zip-duplicates");
+        let java = dir.path().join("jdk/bin/java");
+
+        extract_zip(DUPLICATED, dir.path(), DuplicateEntryPolicy::KeepFirst).unwrap();
+        assert_eq!(std::fs::read_to_string(&java).unwrap(), "first");
+
+        extract_zip(DUPLICATED, dir.path(), DuplicateEntryPolicy::KeepLast).unwrap();
+        assert_eq!(std::fs::read_to_string(&java).unwrap(), "second");
+
+        let error = extract_zip(DUPLICATED, dir.path(), DuplicateEntryPolicy::Error).unwrap_err();
+        assert!(matches!(error.downcast_ref::<JavaSetupError>(), Some(JavaSetupError::DuplicateEntry(_))));
+    }
+
+    #[test]
+    fn zip_entries_climbing_out_are_refused() {
+        let dir = TempDir::new("zip-slip");
+        let destination = dir.path().join("extract");
+        for name in ["../evil.txt", "jdk/../../evil.txt", "/evil.txt"] {
+            let entries = [("jdk/release", "17"), (name, "evil")];
+            let error = extract_zip(&entries, &destination, DuplicateEntryPolicy::default()).unwrap_err();
+            assert!(
+                matches!(error.downcast_ref::<JavaSetupError>(), Some(JavaSetupError::UnsafeArchiveEntry(entry)) if entry == name),
+                "{} was not refused: {}",
+                name,
+                error
+            );
+        }
+        assert!(!dir.path().join("evil.txt").exists());
+        assert!(!Path::new("/evil.txt").exists());
+        // Nothing but the safe entry was written
+        let written: Vec<PathBuf> = walkdir::WalkDir::new(dir.path())
+            .into_iter()
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| entry.file_type().is_file())
+            .map(|entry| entry.into_path())
+            .collect();
+        assert_eq!(written, vec![destination.join("jdk/release")]);
+    }
+}
@@ -0,0 +1,121 @@
+use std::path::PathBuf;
+
+use crate::{ImageType, JavaSetup, JavaSetupError, Vendor};
+
+// Builds a JavaSetup from named settings, see JavaSetup::builder. Only the version and the
+// install path are required, the download and extract paths default to a directory under temp_dir()
+#[derive(Debug, Clone, Default)]
+pub struct JavaSetupBuilder {
+    version: Option<String>,
+    download_path: Option<String>,
+    extract_path: Option<String>,
+    install_path: Option<String>,
+    vendor: Option<Vendor>,
+    image_type: Option<ImageType>,
+    arch: Option<String>,
+    force: bool,
+    proxy: Option<String>,
+}
+
+impl JavaSetupBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Feature release to install, e.g. 17
+    pub fn version(mut self, version: &str) -> Self {
+        self.version = Some(version.to_string());
+        self
+    }
+
+    // A trailing separator makes it a directory, the archive then keeps the server's file name
+    pub fn download_path(mut self, download_path: &str) -> Self {
+        self.download_path = Some(download_path.to_string());
+        self
+    }
+
+    pub fn extract_path(mut self, extract_path: &str) -> Self {
+        self.extract_path = Some(extract_path.to_string());
+        self
+    }
+
+    pub fn install_path(mut self, install_path: &str) -> Self {
+        self.install_path = Some(install_path.to_string());
+        self
+    }
+
+    // See JavaSetup::set_jdk_vendor
+    pub fn vendor(mut self, vendor: Vendor) -> Self {
+        self.vendor = Some(vendor);
+        self
+    }
+
+    // See JavaSetup::set_image_type
+    pub fn image_type(mut self, image_type: ImageType) -> Self {
+        self.image_type = Some(image_type);
+        self
+    }
+
+    // Adoptium architecture name, see JavaSetup::set_architecture
+    pub fn arch(mut self, arch: &str) -> Self {
+        self.arch = Some(arch.to_string());
+        self
+    }
+
+    // See JavaSetup::set_force
+    pub fn force(mut self, force: bool) -> Self {
+        self.force = force;
+        self
+    }
+
+    // Explicit proxy URL, see JavaSetup::set_proxy. Without one the proxy environment variables apply
+    pub fn proxy(mut self, proxy_url: &str) -> Self {
+        self.proxy = Some(proxy_url.to_string());
+        self
+    }
+
+    // Fails with MissingSetting when the version or the install path is missing or empty, and with
+    // InvalidProxy for a proxy URL reqwest can't use
+    pub fn build(self) -> Result<JavaSetup, JavaSetupError> {
+        let version = required(self.version, "version")?;
+        let install_path = required(self.install_path, "install_path")?;
+        let work_dir = default_work_dir(&version);
+        let download_path = self.download_path.unwrap_or_else(|| {
+            // Ends with a separator so the archive keeps its own name
+            let mut download_dir = work_dir.join("download").to_string_lossy().into_owned();
+            download_dir.push(std::path::MAIN_SEPARATOR);
+            download_dir
+        });
+        let extract_path = self
+            .extract_path
+            .unwrap_or_else(|| work_dir.join("extract").to_string_lossy().into_owned());
+
+        let mut setup = JavaSetup::new(&version, &download_path, &extract_path, &install_path);
+        if let Some(vendor) = self.vendor {
+            setup.set_jdk_vendor(vendor);
+        }
+        if let Some(image_type) = self.image_type {
+            setup.set_image_type(image_type);
+        }
+        if let Some(arch) = &self.arch {
+            setup.set_architecture(arch);
+        }
+        setup.set_force(self.force);
+        if let Some(proxy_url) = &self.proxy {
+            setup.set_proxy(Some(proxy_url))?;
+        }
+        Ok(setup)
+    }
+}
+
+fn required(value: Option<String>, name: &'static str) -> Result<String, JavaSetupError> {
+    value
+        .filter(|value| !value.trim().is_empty())
+        .ok_or(JavaSetupError::MissingSetting(name))
+}
+
+// One directory per version, so setups of different versions don't share an extract path
+fn default_work_dir(version: &str) -> PathBuf {
+    let version = version.replace(|c: char| !c.is_ascii_alphanumeric() && c != '.', "_");
+    std::env::temp_dir().join("launcher-java-installer").join(format!("java-{}", version))
+}
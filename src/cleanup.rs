@@ -0,0 +1,43 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use log::{info, warn};
+
+// Tracks the partial artifacts of a running setup so they can be removed if it gets interrupted
+#[derive(Debug, Clone, Default)]
+pub struct CleanupGuard {
+    paths: Arc<Mutex<Vec<PathBuf>>>,
+}
+
+impl CleanupGuard {
+    pub fn track(&self, path: &Path) {
+        let mut paths = self.paths.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        if !paths.iter().any(|tracked| tracked == path) {
+            paths.push(path.to_path_buf());
+        }
+    }
+
+    // Forgets every tracked path, called once the artifacts are complete or cleaned up normally
+    pub fn clear(&self) {
+        self.paths.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).clear();
+    }
+
+    // Removes every tracked file or directory that still exists
+    pub fn cleanup(&self) {
+        let paths: Vec<PathBuf> = std::mem::take(&mut *self.paths.lock().unwrap_or_else(|poisoned| poisoned.into_inner()));
+        for path in paths {
+            let result = if path.is_dir() {
+                fs::remove_dir_all(&path)
+            } else if path.exists() {
+                fs::remove_file(&path)
+            } else {
+                Ok(())
+            };
+            match result {
+                Ok(()) => info!("Removed partial file {}", path.display()),
+                Err(e) => warn!("Could not remove {}: {}", path.display(), e),
+            }
+        }
+    }
+}
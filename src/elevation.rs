@@ -0,0 +1,93 @@
+// Re-runs the final copy of an install elevated when the target needs administrator rights
+
+use std::path::Path;
+
+use crate::error::error_chain;
+
+// Whether copy_elevated can prompt for elevation on this platform
+pub(crate) const SUPPORTED: bool = cfg!(windows);
+
+// Whether the error, or any error it wraps, is an access-denied io error
+pub(crate) fn is_permission_denied(error: &(dyn std::error::Error + 'static)) -> bool {
+    error_chain(error).any(|error| {
+        error
+            .downcast_ref::<std::io::Error>()
+            .is_some_and(|error| error.kind() == std::io::ErrorKind::PermissionDenied)
+    })
+}
+
+#[cfg(windows)]
+mod imp {
+    use std::path::Path;
+
+    use windows::Win32::Foundation::CloseHandle;
+    use windows::Win32::System::Threading::{GetExitCodeProcess, INFINITE, WaitForSingleObject};
+    use windows::Win32::UI::Shell::{SEE_MASK_NOASYNC, SEE_MASK_NOCLOSEPROCESS, SHELLEXECUTEINFOW, ShellExecuteExW};
+    use windows::Win32::UI::WindowsAndMessaging::SW_HIDE;
+    use windows::core::PCWSTR;
+
+    // robocopy exit codes from 8 up mean at least one file failed to copy
+    const ROBOCOPY_FAILED: u32 = 8;
+
+    fn wide(s: &str) -> Vec<u16> {
+        s.encode_utf16().chain(std::iter::once(0)).collect()
+    }
+
+    // A quoted robocopy argument, a trailing backslash would escape the closing quote
+    fn quote(path: &Path) -> String {
+        format!("\"{}\"", path.to_string_lossy().trim_end_matches('\\'))
+    }
+
+    pub fn copy_elevated(source: &Path, target: &Path) -> std::io::Result<()> {
+        let source = std::path::absolute(source)?;
+        let target = std::path::absolute(target)?;
+        // /MIR also deletes whatever a previous install left in target
+        let parameters = format!("{} {} /MIR /NFL /NDL /NJH /NJS /NP /R:2 /W:1", quote(&source), quote(&target));
+
+        let verb = wide("runas");
+        let file = wide("robocopy.exe");
+        let parameters = wide(&parameters);
+        let mut info = SHELLEXECUTEINFOW {
+            cbSize: std::mem::size_of::<SHELLEXECUTEINFOW>() as u32,
+            fMask: SEE_MASK_NOCLOSEPROCESS | SEE_MASK_NOASYNC,
+            lpVerb: PCWSTR(verb.as_ptr()),
+            lpFile: PCWSTR(file.as_ptr()),
+            lpParameters: PCWSTR(parameters.as_ptr()),
+            nShow: SW_HIDE.0,
+            ..Default::default()
+        };
+
+        // Fails with ERROR_CANCELLED when the user declines the UAC prompt
+        unsafe { ShellExecuteExW(&mut info) }.map_err(std::io::Error::from)?;
+        if info.hProcess.is_invalid() {
+            return Err(std::io::Error::other("the elevated copy did not start"));
+        }
+
+        let mut exit_code = 0;
+        let result = unsafe {
+            WaitForSingleObject(info.hProcess, INFINITE);
+            GetExitCodeProcess(info.hProcess, &mut exit_code)
+        };
+        let _ = unsafe { CloseHandle(info.hProcess) };
+        result.map_err(std::io::Error::from)?;
+
+        if exit_code >= ROBOCOPY_FAILED {
+            return Err(std::io::Error::other(format!("the elevated copy failed with robocopy exit code {}", exit_code)));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(not(windows))]
+mod imp {
+    use std::path::Path;
+
+    pub fn copy_elevated(_source: &Path, _target: &Path) -> std::io::Result<()> {
+        Err(std::io::Error::new(std::io::ErrorKind::Unsupported, "elevated installs are only supported on Windows"))
+    }
+}
+
+// Mirrors source into target from an elevated process, prompting through UAC, and waits for it
+pub(crate) fn copy_elevated(source: &Path, target: &Path) -> std::io::Result<()> {
+    imp::copy_elevated(source, target)
+}
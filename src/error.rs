@@ -0,0 +1,250 @@
+use std::fmt;
+
+// Errors reported by the Java setup process
+#[derive(Debug)]
+pub enum JavaSetupError {
+    // No binary of the metadata response has the requested package name
+    AssetNotFound(String),
+    // The pre-download confirmation callback rejected the resolved package
+    Declined,
+    // The setup's CancellationToken was cancelled before the JDK was installed
+    Cancelled,
+    // The installed java could not be launched or reported an unexpected version
+    VerificationFailed(String),
+    // The checksum policy requires a checksum but the package has none
+    ChecksumMissing(String),
+    // The downloaded file does not match the published SHA-256 checksum
+    ChecksumMismatch { expected: String, actual: String },
+    // No archive handler is registered for the downloaded file's extension
+    UnsupportedArchive(String),
+    // No extracted directory containing a JDK 'bin' folder was found
+    JdkDirNotFound,
+    // install_path already exists and the policy forbids touching it
+    InstallExists(String),
+    // uninstall found no manifest in install_path, so it didn't come from this crate
+    UnmanagedInstall(String),
+    // The install has no hash index to verify against
+    HashIndexMissing(String),
+    // The lockfile can't be parsed
+    InvalidLockfile(String),
+    // The configured environment does not point at a working JDK
+    EnvMisconfigured(String),
+    // A certificate pin is not a hex SHA-256 fingerprint
+    InvalidFingerprint(String),
+    // The proxy given to set_proxy is not a valid proxy URL
+    InvalidProxy(String),
+    // The app id must be a single plain directory name
+    InvalidAppId(String),
+    // JavaSetupBuilder::build was called without this required setting
+    MissingSetting(&'static str),
+    // A metadata response exceeded the configured size limit
+    ResponseTooLarge { url: String, limit: u64 },
+    // The server answered 429, retry_after is the wait its Retry-After header asked for
+    RateLimited { url: String, retry_after: Option<std::time::Duration> },
+    // A file name appears twice in the archive and the policy forbids it
+    DuplicateEntry(String),
+    // An archive entry is absolute or would be extracted outside the extract path
+    UnsafeArchiveEntry(String),
+    // Writing to the target was denied and elevation is disabled or unavailable
+    NeedsElevation { operation: String, reason: String },
+    // The downloaded archive stayed locked or disappeared, usually antivirus quarantining it
+    FileQuarantined(String),
+    // The extracted JDK root lacks entries every JDK has, the archive is wrong or truncated
+    UnexpectedArchiveLayout { root: String, missing: Vec<String> },
+    // The connectivity check couldn't get an answer from the API
+    ApiUnreachable { url: String, failure: ConnectivityFailure, detail: String },
+    // The feature release has binaries for the OS, but none for the requested architecture
+    ArchitectureUnavailable { architecture: String, java_version: String, available: Vec<String> },
+    // The release metadata has no package for the requested version
+    DownloadLinkNotFound(String),
+    // A PowerShell script changing the environment exited with an error, or there is no shell profile
+    EnvUpdateFailed(String),
+    Http(reqwest::Error),
+    Io(std::io::Error),
+    Zip(zip::result::ZipError),
+    Json(serde_json::Error),
+    // Anything else, e.g. an error returned by a custom JdkSource or ArchiveExtractor
+    Other(Box<dyn std::error::Error + Send + Sync>),
+}
+
+// Why the API couldn't be reached
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConnectivityFailure {
+    // The host name didn't resolve
+    Dns,
+    // The handshake failed or the certificate was rejected (including pin mismatches)
+    Tls,
+    Timeout,
+    // Resolved but refused or unreachable
+    Connect,
+    // The server answered with an error status
+    Http(u16),
+    Other,
+}
+
+impl fmt::Display for ConnectivityFailure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConnectivityFailure::Dns => write!(f, "DNS lookup failed"),
+            ConnectivityFailure::Tls => write!(f, "TLS handshake failed"),
+            ConnectivityFailure::Timeout => write!(f, "timed out"),
+            ConnectivityFailure::Connect => write!(f, "connection failed"),
+            ConnectivityFailure::Http(status) => write!(f, "HTTP status {}", status),
+            ConnectivityFailure::Other => write!(f, "request failed"),
+        }
+    }
+}
+
+impl fmt::Display for JavaSetupError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            JavaSetupError::AssetNotFound(name) => write!(f, "no binary named {} in the release metadata", name),
+            JavaSetupError::Declined => write!(f, "the download was declined"),
+            JavaSetupError::Cancelled => write!(f, "the setup was cancelled"),
+            JavaSetupError::VerificationFailed(reason) => write!(f, "installation verification failed: {}", reason),
+            JavaSetupError::ChecksumMissing(name) => write!(f, "no checksum published for {}", name),
+            JavaSetupError::ChecksumMismatch { expected, actual } => {
+                write!(f, "checksum mismatch: expected {}, got {}", expected, actual)
+            }
+            JavaSetupError::UnsupportedArchive(name) => write!(f, "unsupported archive format: {}", name),
+            JavaSetupError::JdkDirNotFound => write!(f, "extracted JDK folder not found"),
+            JavaSetupError::InstallExists(path) => write!(f, "an install already exists at {}", path),
+            JavaSetupError::UnmanagedInstall(path) => write!(f, "{} was not installed by this crate, refusing to remove it", path),
+            JavaSetupError::HashIndexMissing(path) => write!(f, "no hash index found in {}", path),
+            JavaSetupError::InvalidLockfile(path) => write!(f, "invalid lockfile: {}", path),
+            JavaSetupError::EnvMisconfigured(reason) => write!(f, "environment misconfigured: {}", reason),
+            JavaSetupError::InvalidFingerprint(fingerprint) => write!(f, "invalid certificate fingerprint: {}", fingerprint),
+            JavaSetupError::InvalidProxy(proxy_url) => write!(f, "invalid proxy url: {}", proxy_url),
+            JavaSetupError::InvalidAppId(app_id) => write!(f, "invalid app id: {}", app_id),
+            JavaSetupError::MissingSetting(name) => write!(f, "{} must be set before building the setup", name),
+            JavaSetupError::ResponseTooLarge { url, limit } => {
+                write!(f, "response from {} is larger than {} bytes", url, limit)
+            }
+            JavaSetupError::RateLimited { url, .. } => write!(f, "{} answered too many requests", url),
+            JavaSetupError::DuplicateEntry(name) => write!(f, "duplicate archive entry: {}", name),
+            JavaSetupError::UnsafeArchiveEntry(name) => write!(f, "archive entry {} would be extracted outside the extract path", name),
+            JavaSetupError::NeedsElevation { operation, reason } => {
+                write!(f, "{} needs administrator rights: {}", operation, reason)
+            }
+            JavaSetupError::FileQuarantined(path) => write!(
+                f,
+                "{} was locked or removed right after the download, likely by antivirus software; \
+                 consider excluding the download directory from scanning",
+                path
+            ),
+            JavaSetupError::UnexpectedArchiveLayout { root, missing } => {
+                write!(f, "unexpected archive layout: {} has no {}", root, missing.join(", "))
+            }
+            JavaSetupError::ApiUnreachable { url, failure, detail } => {
+                write!(f, "can't reach {}: {} ({})", url, failure, detail)
+            }
+            JavaSetupError::ArchitectureUnavailable { architecture, java_version, available } => write!(
+                f,
+                "no {} build of Java {}, available architectures: {}",
+                architecture,
+                java_version,
+                available.join(", ")
+            ),
+            JavaSetupError::DownloadLinkNotFound(java_version) => write!(f, "no download link found for Java {}", java_version),
+            JavaSetupError::EnvUpdateFailed(reason) => write!(f, "could not update the environment: {}", reason),
+            JavaSetupError::Http(e) => write!(f, "request failed: {}", e),
+            JavaSetupError::Io(e) => write!(f, "{}", e),
+            JavaSetupError::Zip(e) => write!(f, "invalid zip archive: {}", e),
+            JavaSetupError::Json(e) => write!(f, "invalid JSON: {}", e),
+            JavaSetupError::Other(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for JavaSetupError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            JavaSetupError::Http(e) => Some(e),
+            JavaSetupError::Io(e) => Some(e),
+            JavaSetupError::Zip(e) => Some(e),
+            JavaSetupError::Json(e) => Some(e),
+            JavaSetupError::Other(e) => Some(e.as_ref()),
+            _ => None,
+        }
+    }
+}
+
+impl From<reqwest::Error> for JavaSetupError {
+    fn from(e: reqwest::Error) -> Self {
+        JavaSetupError::Http(e)
+    }
+}
+
+impl From<std::io::Error> for JavaSetupError {
+    fn from(e: std::io::Error) -> Self {
+        JavaSetupError::Io(e)
+    }
+}
+
+impl From<zip::result::ZipError> for JavaSetupError {
+    fn from(e: zip::result::ZipError) -> Self {
+        JavaSetupError::Zip(e)
+    }
+}
+
+impl From<serde_json::Error> for JavaSetupError {
+    fn from(e: serde_json::Error) -> Self {
+        JavaSetupError::Json(e)
+    }
+}
+
+// The stages still pass boxed errors around internally, this recovers the concrete type
+impl From<Box<dyn std::error::Error + Send + Sync>> for JavaSetupError {
+    fn from(e: Box<dyn std::error::Error + Send + Sync>) -> Self {
+        let e = match e.downcast::<JavaSetupError>() {
+            Ok(e) => return *e,
+            Err(e) => e,
+        };
+        let e = match e.downcast::<reqwest::Error>() {
+            Ok(e) => return JavaSetupError::Http(*e),
+            Err(e) => e,
+        };
+        let e = match e.downcast::<std::io::Error>() {
+            Ok(e) => return JavaSetupError::Io(*e),
+            Err(e) => e,
+        };
+        let e = match e.downcast::<zip::result::ZipError>() {
+            Ok(e) => return JavaSetupError::Zip(*e),
+            Err(e) => e,
+        };
+        match e.downcast::<serde_json::Error>() {
+            Ok(e) => JavaSetupError::Json(*e),
+            Err(e) => JavaSetupError::Other(e),
+        }
+    }
+}
+
+// The error followed by every error it wraps, outermost first
+pub(crate) fn error_chain<'a>(
+    error: &'a (dyn std::error::Error + 'static),
+) -> impl Iterator<Item = &'a (dyn std::error::Error + 'static)> {
+    std::iter::successors(Some(error), |error| {
+        // io::Error::source skips over the error it wraps, so unwrap it by hand
+        match error.downcast_ref::<std::io::Error>().and_then(|error| error.get_ref()) {
+            Some(inner) => Some(inner as &(dyn std::error::Error + 'static)),
+            None => error.source(),
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn error_chain_unwraps_io_errors() {
+        let denied = std::io::Error::from(std::io::ErrorKind::PermissionDenied);
+        let error = std::io::Error::other(JavaSetupError::Io(denied));
+        let kinds: Vec<Option<std::io::ErrorKind>> = error_chain(&error)
+            .map(|error| error.downcast_ref::<std::io::Error>().map(|error| error.kind()))
+            .collect();
+        assert_eq!(kinds, vec![Some(std::io::ErrorKind::Other), None, Some(std::io::ErrorKind::PermissionDenied)]);
+        assert!(crate::elevation::is_permission_denied(&error));
+        assert!(!crate::elevation::is_permission_denied(&std::io::Error::from(std::io::ErrorKind::NotFound)));
+    }
+}
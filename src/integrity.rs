@@ -0,0 +1,304 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use rayon::prelude::*;
+use sha2::{Digest, Sha256};
+use walkdir::WalkDir;
+
+use crate::manifest::{write_atomically, InstallManifest, MANIFEST_FILE};
+use crate::{verify_installation, JavaSetupError};
+
+// Name of the per-file hash index written at the root of an install
+pub const HASH_INDEX_FILE: &str = ".java-setup-hashes.json";
+
+// SHA-256 of every installed file, keyed by its path relative to the install root
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct HashIndex {
+    pub files: BTreeMap<String, String>,
+}
+
+// Files of an install that no longer match its hash index
+#[derive(Debug, Clone, Default)]
+pub struct IntegrityReport {
+    pub missing: Vec<String>,
+    pub modified: Vec<String>,
+}
+
+impl IntegrityReport {
+    pub fn is_intact(&self) -> bool {
+        self.missing.is_empty() && self.modified.is_empty()
+    }
+
+    // Files that have to be copied again to repair the install
+    pub fn damaged(&self) -> impl Iterator<Item = &String> {
+        self.missing.iter().chain(self.modified.iter())
+    }
+}
+
+enum FileState {
+    Intact,
+    Missing,
+    Modified,
+}
+
+impl HashIndex {
+    pub fn path(install_path: &Path) -> PathBuf {
+        install_path.join(HASH_INDEX_FILE)
+    }
+
+    // Hashes every file of the install except the crate's own bookkeeping files
+    pub fn build(install_path: &Path) -> std::io::Result<Self> {
+        Self::build_with_threads(install_path, None)
+    }
+
+    // Like build, hashing on at most threads threads (None uses one per CPU)
+    pub fn build_with_threads(install_path: &Path, threads: Option<usize>) -> std::io::Result<Self> {
+        let keys = list_files(install_path)?;
+        let files = hash_pool(threads)?.install(|| {
+            keys.into_par_iter()
+                .map(|key| {
+                    let hash = sha256_file(&install_path.join(&key))?;
+                    Ok((key, hash))
+                })
+                .collect::<std::io::Result<BTreeMap<_, _>>>()
+        })?;
+        Ok(HashIndex { files })
+    }
+
+    // Reads the index of an install, None when it is missing or corrupt
+    pub fn read(install_path: &Path) -> Option<Self> {
+        let content = fs::read_to_string(Self::path(install_path)).ok()?;
+        let json: serde_json::Value = serde_json::from_str(&content).ok()?;
+        let mut files = BTreeMap::new();
+        for (path, hash) in json.get("files")?.as_object()? {
+            files.insert(path.clone(), hash.as_str()?.to_string());
+        }
+        Some(HashIndex { files })
+    }
+
+    pub fn write(&self, install_path: &Path) -> std::io::Result<()> {
+        let content = serde_json::to_string_pretty(&serde_json::json!({ "files": self.files }))?;
+        write_atomically(&Self::path(install_path), &content)
+    }
+
+    // Recomputes the hashes of install_path and compares them against this index
+    pub fn verify(&self, install_path: &Path) -> std::io::Result<IntegrityReport> {
+        self.verify_with_threads(install_path, None)
+    }
+
+    // Like verify, hashing on at most threads threads (None uses one per CPU)
+    pub fn verify_with_threads(&self, install_path: &Path, threads: Option<usize>) -> std::io::Result<IntegrityReport> {
+        let entries: Vec<(&String, &String)> = self.files.iter().collect();
+        let states = hash_pool(threads)?.install(|| {
+            entries
+                .par_iter()
+                .map(|(key, expected)| {
+                    let path = install_path.join(key);
+                    if !path.is_file() {
+                        Ok(FileState::Missing)
+                    } else if sha256_file(&path)? != **expected {
+                        Ok(FileState::Modified)
+                    } else {
+                        Ok(FileState::Intact)
+                    }
+                })
+                .collect::<std::io::Result<Vec<_>>>()
+        })?;
+
+        let mut report = IntegrityReport::default();
+        for ((key, _), state) in entries.into_iter().zip(states) {
+            match state {
+                FileState::Missing => report.missing.push(key.clone()),
+                FileState::Modified => report.modified.push(key.clone()),
+                FileState::Intact => {}
+            }
+        }
+        Ok(report)
+    }
+}
+
+// Dedicated pool so a thread cap doesn't affect the caller's global rayon pool
+fn hash_pool(threads: Option<usize>) -> std::io::Result<rayon::ThreadPool> {
+    rayon::ThreadPoolBuilder::new()
+        .num_threads(threads.unwrap_or(0))
+        .thread_name(|index| format!("java-setup-hash-{}", index))
+        .build()
+        .map_err(std::io::Error::other)
+}
+
+// Index keys always use '/' so an index can be compared across platforms
+fn index_key(rel_path: &Path) -> String {
+    rel_path
+        .components()
+        .map(|component| component.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+// Lowercase hex SHA-256 digest of a file
+pub(crate) fn sha256_file(path: &Path) -> std::io::Result<String> {
+    let mut hasher = Sha256::new();
+    std::io::copy(&mut fs::File::open(path)?, &mut hasher)?;
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+// Known-good description of an install: the version java reports and the hash of every file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Lockfile {
+    pub java_version: String,
+    // A None hash only requires the file to be present
+    pub files: BTreeMap<String, Option<String>>,
+}
+
+impl Lockfile {
+    // Captures the current state of a (trusted) install
+    pub fn from_install(install_path: &Path) -> Result<Self, JavaSetupError> {
+        let java_version = verify_installation(install_path)?;
+        let files = HashIndex::build(install_path)?
+            .files
+            .into_iter()
+            .map(|(path, hash)| (path, Some(hash)))
+            .collect();
+        Ok(Lockfile { java_version, files })
+    }
+
+    pub fn read(path: &Path) -> Result<Self, JavaSetupError> {
+        let invalid = || JavaSetupError::InvalidLockfile(path.display().to_string());
+        let json: serde_json::Value = serde_json::from_str(&fs::read_to_string(path)?).map_err(|_| invalid())?;
+
+        let java_version = json.get("java_version").and_then(|version| version.as_str()).ok_or_else(invalid)?;
+        let mut files = BTreeMap::new();
+        for (file, hash) in json.get("files").and_then(|files| files.as_object()).ok_or_else(invalid)? {
+            let hash = match hash {
+                serde_json::Value::Null => None,
+                hash => Some(hash.as_str().ok_or_else(invalid)?.to_string()),
+            };
+            files.insert(file.clone(), hash);
+        }
+        Ok(Lockfile { java_version: java_version.to_string(), files })
+    }
+
+    pub fn write(&self, path: &Path) -> std::io::Result<()> {
+        let content = serde_json::to_string_pretty(&serde_json::json!({
+            "java_version": self.java_version,
+            "files": self.files,
+        }))?;
+        write_atomically(path, &content)
+    }
+}
+
+// Differences between an install and its lockfile
+#[derive(Debug, Clone, Default)]
+pub struct AuditReport {
+    pub expected_version: String,
+    // Version reported by java -version, None if it couldn't be run
+    pub actual_version: Option<String>,
+    // Version recorded in the install manifest, if the install has one
+    pub manifest_version: Option<String>,
+    pub missing: Vec<String>,
+    pub modified: Vec<String>,
+    // Files present in the install but not listed in the lockfile
+    pub unexpected: Vec<String>,
+    pub hashes_verified: bool,
+}
+
+impl AuditReport {
+    pub fn version_matches(&self) -> bool {
+        self.actual_version.as_deref() == Some(self.expected_version.as_str())
+    }
+
+    pub fn is_clean(&self) -> bool {
+        self.version_matches() && self.missing.is_empty() && self.modified.is_empty() && self.unexpected.is_empty()
+    }
+}
+
+// Compares an install against a lockfile without touching the network
+pub(crate) fn audit_install(install_path: &Path, lockfile: &Lockfile, verify_hashes: bool) -> std::io::Result<AuditReport> {
+    let mut report = AuditReport {
+        expected_version: lockfile.java_version.clone(),
+        actual_version: verify_installation(install_path).ok(),
+        manifest_version: InstallManifest::read(install_path).map(|manifest| manifest.java_version),
+        hashes_verified: verify_hashes,
+        ..AuditReport::default()
+    };
+
+    let present = list_files(install_path)?;
+    for (key, expected) in &lockfile.files {
+        if !present.contains(key) {
+            report.missing.push(key.clone());
+        } else if verify_hashes
+            && let Some(expected) = expected
+            && sha256_file(&install_path.join(key))? != *expected
+        {
+            report.modified.push(key.clone());
+        }
+    }
+    report.unexpected = present
+        .into_iter()
+        .filter(|key| !lockfile.files.contains_key(key))
+        .collect();
+    Ok(report)
+}
+
+// Index keys of the files of an install, leaving out the crate's bookkeeping files
+fn list_files(install_path: &Path) -> std::io::Result<Vec<String>> {
+    let mut files = Vec::new();
+    for entry in WalkDir::new(install_path).min_depth(1).sort_by_file_name() {
+        let entry = entry?;
+        if !entry.file_type().is_file() {
+            continue;
+        }
+        let key = index_key(entry.path().strip_prefix(install_path).unwrap());
+        if key != MANIFEST_FILE && key != HASH_INDEX_FILE {
+            files.push(key);
+        }
+    }
+    Ok(files)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{write_file, TempDir};
+
+    // A JDK-sized tree: many small files, a few large ones like lib/modules
+    fn generate_tree(root: &Path, files: usize, large_files: usize) {
+        for i in 0..files {
+            write_file(root, &format!("lib/pkg{}/file{}.class", i % 50, i), &"x".repeat(4096 + i));
+        }
+        for i in 0..large_files {
+            write_file(root, &format!("lib/large{}", i), &"y".repeat(1024 * 1024));
+        }
+    }
+
+    #[test]
+    fn parallel_and_single_threaded_indexes_match() {
+        let dir = TempDir::new("hash-index");
+        generate_tree(dir.path(), 200, 1);
+        let sequential = HashIndex::build_with_threads(dir.path(), Some(1)).unwrap();
+        let parallel = HashIndex::build_with_threads(dir.path(), Some(4)).unwrap();
+        assert_eq!(sequential, parallel);
+        assert_eq!(sequential.files.len(), 201);
+        assert!(parallel.verify_with_threads(dir.path(), Some(4)).unwrap().is_intact());
+    }
+
+    // Run with cargo test --release -- --ignored --nocapture
+    #[test]
+    #[ignore]
+    fn bench_sequential_against_parallel_hashing() {
+        let dir = TempDir::new("hash-bench");
+        generate_tree(dir.path(), 5_000, 64);
+        // Warm the page cache so the first run isn't penalized for reading from disk
+        HashIndex::build_with_threads(dir.path(), None).unwrap();
+
+        for (label, threads) in [("sequential", Some(1)), ("rayon", None)] {
+            let started = std::time::Instant::now();
+            let index = HashIndex::build_with_threads(dir.path(), threads).unwrap();
+            let built = started.elapsed();
+            let started = std::time::Instant::now();
+            index.verify_with_threads(dir.path(), threads).unwrap();
+            println!("{:<10} build {:>8.1?}  verify {:>8.1?}", label, built, started.elapsed());
+        }
+    }
+}
@@ -0,0 +1,55 @@
+use std::path::{Path, PathBuf};
+
+// The standard layout of an installed JDK, paths are computed and may not exist (e.g. jmods in a JRE)
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct InstalledJdk {
+    root: PathBuf,
+}
+
+impl InstalledJdk {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        InstalledJdk { root: root.into() }
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    pub fn bin_dir(&self) -> PathBuf {
+        self.root.join("bin")
+    }
+
+    pub fn java_exe(&self) -> PathBuf {
+        self.bin_dir().join(executable("java"))
+    }
+
+    pub fn javac_exe(&self) -> PathBuf {
+        self.bin_dir().join(executable("javac"))
+    }
+
+    pub fn lib_dir(&self) -> PathBuf {
+        self.root.join("lib")
+    }
+
+    pub fn conf_dir(&self) -> PathBuf {
+        self.root.join("conf")
+    }
+
+    pub fn include_dir(&self) -> PathBuf {
+        self.root.join("include")
+    }
+
+    pub fn jmods_dir(&self) -> PathBuf {
+        self.root.join("jmods")
+    }
+
+    // Key=value file describing the build (JAVA_VERSION, IMPLEMENTOR, ...)
+    pub fn release_file(&self) -> PathBuf {
+        self.root.join("release")
+    }
+}
+
+// Adds the platform's executable suffix to a tool name
+fn executable(name: &str) -> String {
+    format!("{}{}", name, std::env::consts::EXE_SUFFIX)
+}
@@ -1,260 +1,4129 @@
-use serde_json;
-use reqwest;
-use zip;
+use std::collections::VecDeque;
 use std::fs;
-use std::path::Path;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use log::{debug, error, info, warn};
 use walkdir::WalkDir;
+use vendor::ADOPTIUM_AVAILABLE_RELEASES_URL;
+
+mod archive;
+mod builder;
+mod cleanup;
+mod elevation;
+mod error;
+mod integrity;
+mod layout;
+mod manifest;
+mod pinning;
+mod progress;
+mod registry;
+mod release;
+mod shell_profile;
+mod source;
+mod streaming;
+#[cfg(test)]
+mod test_util;
+mod uninstall;
+mod vendor;
+mod version;
+mod windows_env;
+
+pub use archive::{ArchiveExtractor, ArchiveReader, DuplicateEntryPolicy, ExtractorRegistry, TarGzExtractor, ZipExtractor};
+pub use builder::JavaSetupBuilder;
+pub use cleanup::CleanupGuard;
+pub use error::{ConnectivityFailure, JavaSetupError};
+pub use integrity::{AuditReport, HashIndex, IntegrityReport, Lockfile, HASH_INDEX_FILE};
+pub use layout::InstalledJdk;
+pub use manifest::{InstallManifest, MANIFEST_FILE};
+pub use progress::{ChannelReporter, InstallEvent, NoopReporter, ProgressReporter, Stage};
+pub use registry::{DiscoveredJdk, JdkRegistry};
+pub use release::JdkRelease;
+pub use source::{JdkSource, SourceFuture};
+pub use tokio_util::sync::CancellationToken;
+pub use vendor::{AvailableVersions, PackageQuery, Vendor, VendorApi};
+pub use uninstall::UninstallPreview;
+pub use version::JavaVersion;
+pub use windows_env::EnvScope;
 
 // Handles downloading the JDK package
 struct Downloader {
     pub java_version: String,
     pub download_path: String,
     pub java_url: String,
+    pub max_bytes_per_sec: Option<u64>,
+    pub checksum_policy: ChecksumPolicy,
+    pub cleanup: CleanupGuard,
+    // Exact package name to install instead of the first binary of the response
+    pub asset_name: Option<String>,
+    // Rebuilt with a pinning verifier when certificates are pinned
+    pub client: reqwest::Client,
+    // SHA-256 fingerprints of the accepted server certificates, empty trusts any valid chain
+    pub pinned_certs: Vec<[u8; 32]>,
+    // Proxy for every request, HTTP_PROXY, HTTPS_PROXY and ALL_PROXY are used when None
+    pub proxy: Option<String>,
+    // Largest metadata response read before giving up with ResponseTooLarge
+    pub max_metadata_bytes: u64,
+    // Only releases of this vendor are considered when set
+    pub vendor: Option<String>,
+    // Whose API resolves the packages, Adoptium by default
+    pub jdk_vendor: Vendor,
+    // Adoptium architecture name, the host's by default
+    pub architecture: String,
+    pub image_type: ImageType,
+    // Pre-fetched API response used instead of requesting java_url
+    pub metadata: Option<serde_json::Value>,
+    // Downloads repeated after a checksum mismatch before giving up with ChecksumMismatch
+    pub checksum_redownloads: u32,
+    // Replaces the Adoptium API and the HTTP download of the JDK (not of supplementary packages)
+    pub source: Option<Arc<dyn JdkSource>>,
+    // Consulted after each failed request, download, extract or install attempt, None uses the
+    // built-in backoff with retry_attempts and retry_base_delay
+    pub retry_policy: Option<Arc<RetryFn>>,
+    pub retry_attempts: u32,
+    pub retry_base_delay: Duration,
+    pub progress: Arc<dyn ProgressReporter>,
+    // Stops requests, body reads and retry waits with Cancelled, see JavaSetup::set_cancel_token
+    pub cancel: CancellationToken,
 }
 
+const DEFAULT_MAX_METADATA_BYTES: u64 = 4 * 1024 * 1024;
+const DEFAULT_CHECKSUM_REDOWNLOADS: u32 = 1;
+
 // Handles extracting the downloaded JDK archive
 struct Extractor {
     pub download_path: String,
     pub extract_path: String,
+    pub registry: ExtractorRegistry,
+    pub duplicate_policy: DuplicateEntryPolicy,
+    pub progress: Arc<dyn ProgressReporter>,
+    // Makes reads of the archive fail once cancelled, so any handler stops at its next read
+    pub cancel: CancellationToken,
 }
 
 // Handles installing the JDK to the target directory
 struct Installer {
     pub extract_path: String,
     pub install_path: String,
+    pub copy_filter: Option<CopyFilter>,
+    pub existing_policy: ExistingInstallPolicy,
+    pub mode: InstallMode,
+    // JDK root relative to the extracted tree, replaces the search for a 'bin' folder
+    pub jdk_root_subpath: Option<PathBuf>,
+    // Entries the JDK root must contain, None derives them from image_type
+    pub expected_entries: Option<Vec<String>>,
+    // Taken from the package name, which may differ from the requested one for custom sources
+    pub image_type: ImageType,
+    pub progress: Arc<dyn ProgressReporter>,
+}
+
+// Top-level entries of every JDK image, a JRE lacks include
+const DEFAULT_JDK_ENTRIES: &[&str] = &["bin", "lib", "include", "release"];
+const DEFAULT_JRE_ENTRIES: &[&str] = &["bin", "lib", "release"];
+
+// Adoptium package names carry the image type, e.g. OpenJDK17U-jre_x64_windows_hotspot_17.0.9_9.zip
+fn image_type_of(package_name: &str) -> ImageType {
+    if package_name.to_ascii_lowercase().contains("-jre") {
+        ImageType::Jre
+    } else {
+        ImageType::Jdk
+    }
 }
 
 // Configures environment variables for the JDK
 struct EnvironmentVariableConfigurator {
     pub install_path: String,
+    pub pin_path_order: bool,
+    pub backend: EnvBackend,
+    // When set, a java launcher script is written here and PATH is left untouched
+    pub shim_dir: Option<PathBuf>,
+    // Also changes this process's PATH (inherited by the children it spawns), not just the persistent one
+    pub update_current_process: bool,
+    // Points JAVA_HOME at install_path along with the PATH update
+    pub configure_java_home: bool,
+}
+
+// How persistent environment changes are written
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EnvBackend {
+    // Edits HKCU\Environment directly, no subprocess involved
+    Registry,
+    // Runs [Environment]::SetEnvironmentVariable through a PowerShell script
+    PowerShell,
+    // Appends an export line to ~/.profile and ~/.zshrc, the default on Linux and macOS
+    Profile,
+}
+
+impl Default for EnvBackend {
+    fn default() -> Self {
+        if cfg!(windows) {
+            EnvBackend::Registry
+        } else {
+            EnvBackend::Profile
+        }
+    }
+}
+
+// Separates the entries of PATH
+pub(crate) const PATH_SEPARATOR: &str = if cfg!(windows) { ";" } else { ":" };
+
+// What the download stage hands over to the install stage
+struct FetchedArchive {
+    asset: ReleaseAsset,
+    checksum_verified: bool,
 }
 
+type ConfirmDownload = Box<dyn FnMut(&ReleaseAsset) -> bool + Send + Sync>;
+
 pub struct JavaSetup {
     downloader: Downloader,
     extractor: Extractor,
     installer: Installer,
     env_configurator: EnvironmentVariableConfigurator,
+    // Keeps extract_path after a successful install so it can be inspected
+    keep_extracted: bool,
+    // Writes a per-file hash index so the install can be verified and repaired later
+    write_hash_index: bool,
+    // Caps the threads hashing installed files, None uses one per CPU
+    hash_threads: Option<usize>,
+    // Partial artifacts removed by the signal handler, shared with the downloader
+    cleanup: CleanupGuard,
+    // Extra packages placed under supplementary_path after the JDK install
+    supplementary_packages: Vec<SupplementaryPackage>,
+    supplementary_path: String,
+    // Asked with the resolved package before downloading, returning false aborts the setup
+    confirm_download: Option<ConfirmDownload>,
+    // Checks that JAVA_HOME points at a working JDK once the environment is configured
+    validate_java_home: bool,
+    // Runs the installed java -version and checks the feature release before configuring the environment
+    verify_launch: bool,
+    // Compiles and runs a HelloWorld with the installed JDK before configuring the environment
+    verify_toolchain: bool,
+    // Re-runs the copy into install_path through a UAC prompt when writing there is denied
+    elevate_on_access_denied: bool,
+    // Relative download and extract paths live under temp_dir()/<app_id> when set
+    app_id: Option<String>,
+    // tar.gz packages go from the response straight into the install, see set_streaming_install
+    streaming_install: bool,
+    // Extracts with Extractor::extract_async so the runtime thread isn't blocked for the whole archive
+    yield_during_extract: bool,
+    // Builds the install next to install_path and only swaps it in once java runs, see set_verify_before_swap
+    verify_before_swap: bool,
+    // The staged install must match it (version and file hashes) before it is swapped in
+    expected_lockfile: Option<Lockfile>,
+    // Also gets the outcome of setup, see set_event_sender
+    events: Option<Arc<ChannelReporter>>,
+    // setup reinstalls even when install_path already has a working java of the requested version
+    force: bool,
+    // Installed instead of resolving and downloading a package, see JavaSetup::from_archive
+    local_archive: Option<PathBuf>,
+    // Leaves the downloaded archive and the extracted files in place after the install
+    keep_temp_files: bool,
+    // Archive downloaded by this run, deleted once the install is done
+    downloaded_archive: Option<PathBuf>,
+    // The download directory when this run created it, removed again if nothing else ended up inside
+    new_download_dir: Option<PathBuf>,
+    // The extract directory when this run created it, deleted whole once the install is done
+    new_extract_dir: Option<PathBuf>,
+    // What extract_path already held when this run extracted into it, only the other entries are
+    // deleted afterwards. None until an extraction starts
+    existing_extract_entries: Option<Vec<PathBuf>>,
 }
 
-impl Downloader {
-    pub async fn download(&self) -> Result<(), Box<dyn std::error::Error>> {
-        println!("Download URL: {}", &self.java_url);
-        let body = reqwest::get(&self.java_url).await?.text().await?;
-        println!("JSON response: {}", &body);
-        let json: serde_json::Value = serde_json::from_str(&body)?;
+// Package metadata resolved from the Adoptium API response
+#[derive(Debug, Clone)]
+pub struct ReleaseAsset {
+    // Release the package belongs to, e.g. jdk-17.0.9+9
+    pub release_name: Option<String>,
+    // Vendor reported by the API, e.g. eclipse
+    pub vendor: Option<String>,
+    pub name: String,
+    pub link: String,
+    pub size: Option<u64>,
+    pub checksum: Option<String>,
+}
 
-        // Extracts the JDK download link from the JSON response
-        if let Some(link_str) = json.as_array()
-            .and_then(|array| array.first())
-            .and_then(|item| item.get("binaries"))
-            .and_then(|binaries| binaries.as_array())
-            .and_then(|binaries_array| binaries_array.first())
-            .and_then(|binary| binary.get("package"))
-            .and_then(|package| package.get("link"))
-            .and_then(|link| link.as_str())
-        {
-            println!("JDK download link: {}", link_str);
-            let response = reqwest::get(link_str).await?;
-            let mut file = std::fs::File::create(&self.download_path)?;
-            let content = response.bytes().await?;
-            std::io::copy(&mut content.as_ref(), &mut file)?;
-            println!("JDK downloaded to {}", self.download_path);
+// Which image is downloaded as the Java installation
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ImageType {
+    #[default]
+    Jdk,
+    // Only the runtime, much smaller but without javac and the other development tools
+    Jre,
+}
+
+impl ImageType {
+    // Value of the Adoptium image_type query parameter
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ImageType::Jdk => "jdk",
+            ImageType::Jre => "jre",
+        }
+    }
+}
+
+// Additional Adoptium packages that can be installed next to the JDK
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SupplementaryPackage {
+    Jmods,
+    Sources,
+    StaticLibs,
+    DebugImage,
+    TestImage,
+}
+
+impl SupplementaryPackage {
+    // Value of the Adoptium image_type query parameter for this package
+    pub fn image_type(&self) -> &'static str {
+        match self {
+            SupplementaryPackage::Jmods => "jmods",
+            SupplementaryPackage::Sources => "sources",
+            SupplementaryPackage::StaticLibs => "staticlibs",
+            SupplementaryPackage::DebugImage => "debugimage",
+            SupplementaryPackage::TestImage => "testimage",
+        }
+    }
+}
+
+// What to do with the SHA-256 checksum published for a package
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ChecksumPolicy {
+    // Fails when the response carries no checksum
+    Require,
+    // Verifies when a checksum is published, skips otherwise
+    #[default]
+    VerifyIfPresent,
+    // Never verifies
+    Skip,
+}
+
+// What Installer::install does when install_path already exists
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ExistingInstallPolicy {
+    // Deletes the existing install and replaces it
+    #[default]
+    Replace,
+    // Leaves the existing install alone and installs next to it under a version-suffixed directory
+    KeepVersioned,
+    // Stops with InstallExists before downloading anything
+    FailIfExists,
+}
+
+// How Installer::install places the extracted JDK at install_path
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum InstallMode {
+    #[default]
+    Copy,
+    // Points install_path at the extracted JDK with a directory symlink and keeps extract_path.
+    // Falls back to copying across volumes or when symlinks aren't permitted
+    Link,
+}
+
+// What to do after a download, extract or install attempt failed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RetryDecision {
+    // Tries the stage again after the given delay
+    Retry(Duration),
+    Abort,
+}
+
+// Decides from the error and the number of the failed attempt (starting at 1) whether to retry
+type RetryFn = dyn Fn(&(dyn std::error::Error + Send + Sync + 'static), u32) -> RetryDecision + Send + Sync;
+
+const DEFAULT_RETRY_ATTEMPTS: u32 = 3;
+const DEFAULT_RETRY_BASE_DELAY: Duration = Duration::from_millis(500);
+// Longest wait a Retry-After header can ask for, so a server can't park the setup for hours
+const MAX_RETRY_AFTER: Duration = Duration::from_secs(60);
+
+// Built-in policy: retries network and transient IO errors with exponential backoff (0.5s, 1s, ...)
+pub fn default_retry_policy(error: &(dyn std::error::Error + Send + Sync + 'static), attempt: u32) -> RetryDecision {
+    backoff_retry_policy(error, attempt, DEFAULT_RETRY_ATTEMPTS, DEFAULT_RETRY_BASE_DELAY)
+}
+
+// Retries connection failures, timeouts, dropped transfers, 5xx and 429 answers and transient IO
+// errors until max_attempts attempts failed, waiting base_delay and twice as long after each retry.
+// A 429 waits for its Retry-After instead, up to MAX_RETRY_AFTER
+fn backoff_retry_policy(
+    error: &(dyn std::error::Error + Send + Sync + 'static),
+    attempt: u32,
+    max_attempts: u32,
+    base_delay: Duration,
+) -> RetryDecision {
+    if let Some(JavaSetupError::RateLimited { retry_after, .. }) = error.downcast_ref::<JavaSetupError>() {
+        return if attempt < max_attempts {
+            let delay = retry_after.unwrap_or_else(|| backoff_delay(base_delay, attempt));
+            RetryDecision::Retry(delay.min(MAX_RETRY_AFTER))
         } else {
-            println!("Download link not found.");
+            RetryDecision::Abort
+        };
+    }
+    // Public methods wrap the reqwest and io errors, the stages pass them on as they are
+    let error: &(dyn std::error::Error + Send + Sync + 'static) = match error.downcast_ref::<JavaSetupError>() {
+        Some(JavaSetupError::Http(e)) => e,
+        Some(JavaSetupError::Io(e)) => e,
+        _ => error,
+    };
+    // A pin mismatch won't go away by retrying
+    let transient = if pinning::is_certificate_error(error) {
+        false
+    } else if let Some(error) = error.downcast_ref::<reqwest::Error>() {
+        // A 404 or 403 stays the same, a 5xx is usually an overloaded server or mirror
+        match error.status() {
+            Some(status) => status.is_server_error(),
+            // A connection dropped mid-body surfaces as a decode error
+            None => error.is_connect() || error.is_timeout() || error.is_body() || error.is_decode() || error.is_request(),
         }
-        Ok(())
+    } else if let Some(error) = error.downcast_ref::<std::io::Error>() {
+        is_transient_io_error(error)
+    } else {
+        false
+    };
+
+    if transient && attempt < max_attempts {
+        RetryDecision::Retry(backoff_delay(base_delay, attempt))
+    } else {
+        RetryDecision::Abort
     }
 }
 
-impl Extractor {
-    pub fn extract(&self) -> Result<(), Box<dyn std::error::Error>> {
-        println!("Extracting from {} to {}", &self.download_path, &self.extract_path);
-        let file = std::fs::File::open(&self.download_path)?;
-        let mut archive = zip::ZipArchive::new(file)?;
+fn backoff_delay(base_delay: Duration, attempt: u32) -> Duration {
+    base_delay.saturating_mul(1 << (attempt - 1).min(16))
+}
 
-        // Iterates through the archive and extracts files
-        for i in 0..archive.len() {
-            let mut file = archive.by_index(i)?;
-            let outpath = std::path::Path::new(&self.extract_path).join(file.sanitized_name());
+// Builds the error for a 429 answer from its Retry-After header
+fn rate_limited(response: &reqwest::Response) -> JavaSetupError {
+    let retry_after = response
+        .headers()
+        .get(reqwest::header::RETRY_AFTER)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| parse_retry_after(value, std::time::SystemTime::now()));
+    JavaSetupError::RateLimited { url: response.url().to_string(), retry_after }
+}
 
-            if (*file.name()).ends_with('/') {
-                std::fs::create_dir_all(&outpath)?;
-            } else {
-                if let Some(p) = outpath.parent() {
-                    if !p.exists() {
-                        std::fs::create_dir_all(&p)?;
-                    }
+// Retry-After is either a number of seconds or an HTTP date, a date in the past means now
+fn parse_retry_after(value: &str, now: std::time::SystemTime) -> Option<Duration> {
+    let value = value.trim();
+    if let Ok(seconds) = value.parse::<u64>() {
+        return Some(Duration::from_secs(seconds));
+    }
+    let date = parse_http_date(value)?;
+    Some(date.duration_since(now).unwrap_or(Duration::ZERO))
+}
+
+// Parses the IMF-fixdate form servers must send, e.g. "Sun, 06 Nov 1994 08:49:37 GMT"
+fn parse_http_date(value: &str) -> Option<std::time::SystemTime> {
+    const MONTHS: [&str; 12] = ["Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec"];
+    let mut parts = value.split(' ');
+    parts.next()?.strip_suffix(',')?;
+    let day: i64 = parts.next()?.parse().ok()?;
+    let month_name = parts.next()?;
+    let month = MONTHS.iter().position(|name| *name == month_name)? as i64 + 1;
+    let year: i64 = parts.next()?.parse().ok()?;
+    let time: Vec<i64> = parts.next()?.split(':').map(|part| part.parse().ok()).collect::<Option<_>>()?;
+    if parts.next()? != "GMT" || parts.next().is_some() {
+        return None;
+    }
+    let [hour, minute, second] = time[..] else {
+        return None;
+    };
+    if !(1..=31).contains(&day) || !(0..24).contains(&hour) || !(0..60).contains(&minute) || !(0..61).contains(&second) {
+        return None;
+    }
+    // Days since 1970-01-01 of the civil date, counting years from March so leap days come last
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    let days = era * 146_097 + day_of_era - 719_468;
+    let seconds = u64::try_from(days * 86_400 + hour * 3_600 + minute * 60 + second).ok()?;
+    Some(std::time::UNIX_EPOCH + Duration::from_secs(seconds))
+}
+
+// Classifies a failed request, reqwest only tells timeouts and connect errors apart
+fn connectivity_failure(error: &reqwest::Error) -> ConnectivityFailure {
+    if error.is_timeout() {
+        return ConnectivityFailure::Timeout;
+    }
+    let mut source: Option<&(dyn std::error::Error + 'static)> = Some(error);
+    while let Some(error) = source {
+        if pinning::is_certificate_error(error) || error.downcast_ref::<rustls::Error>().is_some() {
+            return ConnectivityFailure::Tls;
+        }
+        // hyper's connector reports resolver failures as "dns error"
+        if error.to_string().starts_with("dns error") {
+            return ConnectivityFailure::Dns;
+        }
+        source = match error.downcast_ref::<std::io::Error>().and_then(|error| error.get_ref()) {
+            Some(inner) => Some(inner as &(dyn std::error::Error + 'static)),
+            None => error.source(),
+        };
+    }
+    if error.is_connect() {
+        ConnectivityFailure::Connect
+    } else {
+        ConnectivityFailure::Other
+    }
+}
+
+// Runs a blocking stage until it succeeds or the policy gives up
+fn retry_blocking<T>(
+    policy: impl Fn(&(dyn std::error::Error + Send + Sync + 'static), u32) -> RetryDecision,
+    stage: &str,
+    mut operation: impl FnMut() -> Result<T, Box<dyn std::error::Error + Send + Sync>>,
+) -> Result<T, Box<dyn std::error::Error + Send + Sync>> {
+    let mut attempt = 1;
+    loop {
+        match operation() {
+            Ok(value) => return Ok(value),
+            Err(e) => match policy(e.as_ref(), attempt) {
+                RetryDecision::Retry(delay) => {
+                    warn!("{} failed ({}), retrying in {:?}...", stage, e, delay);
+                    std::thread::sleep(delay);
+                    attempt += 1;
                 }
-                let mut outfile = std::fs::File::create(&outpath)?;
-                std::io::copy(&mut file, &mut outfile)?;
-            }
+                RetryDecision::Abort => return Err(e),
+            },
         }
-        println!("JDK extracted to {}", self.extract_path);
-        Ok(())
     }
 }
 
-// Recursively copies all files and directories from src to dst
-fn copy_dir_all(src: &Path, dst: &Path) -> std::io::Result<()> {
-    fs::create_dir_all(dst)?;
-    for entry in WalkDir::new(src) {
-        let entry = entry?;
-        let rel_path = entry.path().strip_prefix(src).unwrap();
-        let dest_path = dst.join(rel_path);
-        if entry.file_type().is_dir() {
-            fs::create_dir_all(&dest_path)?;
-        } else {
-            fs::copy(entry.path(), &dest_path)?;
+// Outcome and timing of one version installed by JavaSetup::install_many
+#[derive(Debug)]
+pub struct BatchInstallResult {
+    pub java_version: String,
+    pub download_time: Duration,
+    pub install_time: Duration,
+    pub result: Result<InstallReport, JavaSetupError>,
+}
+
+// Summary of what a setup run actually did
+#[derive(Debug, Clone, Default)]
+pub struct InstallReport {
+    // Where the JDK was installed, differs from install_path with KeepVersioned
+    pub install_path: PathBuf,
+    // Subpaths of install_path (bin, java, lib, release, ...)
+    pub jdk: InstalledJdk,
+    // IMPLEMENTOR from the JDK's release file, or the vendor reported by the API when it has none
+    pub vendor: Option<String>,
+    // The installed JDK's release file, None when it doesn't ship one
+    pub release: Option<JdkRelease>,
+    pub checksum_verified: bool,
+    // Files left out of the install by the copy filter
+    pub files_skipped: usize,
+    // Files that needed retries to copy, relative to the install root
+    pub files_retried: Vec<PathBuf>,
+    // install_path is a link to the kept extracted JDK, see InstallMode::Link
+    pub linked: bool,
+    // A HelloWorld compiled and ran, false when not requested or the install has no javac
+    pub toolchain_verified: bool,
+    // Persistent scope whose PATH already had the JDK bin, None when the user's PATH was updated
+    pub path_already_configured: Option<EnvScope>,
+    // The launcher written instead of changing PATH, see set_launcher_shim_dir
+    pub launcher_shim: Option<PathBuf>,
+    // Set when PATH order pinning was requested
+    pub path_order: Option<PathOrderVerification>,
+    // Where the current link pointed before install_and_activate switched it
+    pub previous_active: Option<PathBuf>,
+    // install_path already had the requested version, nothing was downloaded or installed
+    pub already_installed: bool,
+    // The version the installed java -version printed, e.g. 17.0.9. None when set_verify_launch is off
+    pub version: Option<String>,
+}
+
+// Result of comparing an install against the latest GA of its feature release
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum UpdateStatus {
+    UpToDate,
+    // from is the installed version, to the latest release name
+    Available { from: String, to: String },
+}
+
+// Outcome of checking that a fresh session resolves the installed java
+#[derive(Debug, Clone)]
+pub struct PathOrderVerification {
+    // java.home of the java found first on the fresh PATH, None if no java was found
+    pub resolved_java: Option<PathBuf>,
+    // Whether the JDK bin had to be moved to the front of PATH
+    pub adjusted: bool,
+    // Whether the resolved java is the installed one
+    pub verified: bool,
+}
+
+// A file written by the Downloader
+#[derive(Debug, Clone)]
+pub struct DownloadedFile {
+    pub path: PathBuf,
+    pub checksum_verified: bool,
+}
+
+// Treats existing directories and paths ending with a separator as directories
+fn is_dir_path(path: &Path) -> bool {
+    let raw = path.as_os_str().to_string_lossy();
+    raw.ends_with('/') || raw.ends_with('\\') || path.is_dir()
+}
+
+// Keeps only the last component of a server-provided name so it can't escape the download directory
+fn safe_file_name(name: &str) -> Option<String> {
+    let name = name.trim().rsplit(['/', '\\']).next()?;
+    if name.is_empty() || name == "." || name == ".." {
+        return None;
+    }
+    Some(name.to_string())
+}
+
+// Parses the file name of a Content-Disposition header, preferring the RFC 5987 filename* form
+fn content_disposition_filename(header: &str) -> Option<String> {
+    let mut plain = None;
+    for param in header.split(';').map(str::trim) {
+        let Some((key, value)) = param.split_once('=') else {
+            continue;
+        };
+        let value = value.trim().trim_matches('"');
+        match key.trim().to_ascii_lowercase().as_str() {
+            // filename*=UTF-8''OpenJDK17U-jdk_x64_windows_hotspot_17.0.9_9.zip
+            "filename*" => {
+                let encoded = value.split_once("''").map(|(_, encoded)| encoded).unwrap_or(value);
+                if let Some(name) = safe_file_name(&percent_decode(encoded)) {
+                    return Some(name);
+                }
+            }
+            "filename" => plain = safe_file_name(value),
+            _ => {}
         }
     }
-    Ok(())
+    plain
 }
 
-impl Installer {
-    pub fn install(&mut self) -> Result<(), Box<dyn std::error::Error>> {
-        println!("Installing from {} to {}", &self.extract_path, &self.install_path);
-        if Path::new(&self.install_path).exists() {
-            fs::remove_dir_all(&self.install_path)?;
+fn percent_decode(value: &str) -> String {
+    let bytes = value.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%'
+            && let Some(byte) = value.get(i + 1..i + 3).and_then(|hex| u8::from_str_radix(hex, 16).ok())
+        {
+            decoded.push(byte);
+            i += 3;
+            continue;
         }
+        decoded.push(bytes[i]);
+        i += 1;
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+// Sent with every request, the GitHub API used for GraalVM refuses requests without one
+pub(crate) const USER_AGENT: &str = concat!("launcher-java-installer/", env!("CARGO_PKG_VERSION"));
+const CONNECTIVITY_TIMEOUT: Duration = Duration::from_secs(10);
+
+// Adoptium's name for the host OS, Rust calls macOS "macos" where the API expects "mac"
+fn adoptium_os() -> &'static str {
+    match std::env::consts::OS {
+        "macos" => "mac",
+        os => os,
+    }
+}
+
+// Adoptium's name for the host architecture
+fn adoptium_arch() -> &'static str {
+    match std::env::consts::ARCH {
+        "x86_64" => "x64",
+        "powerpc64" if cfg!(target_endian = "little") => "ppc64le",
+        "powerpc64" => "ppc64",
+        "sparc64" => "sparcv9",
+        // aarch64, arm, x86, s390x and riscv64 are spelled the same
+        arch => arch,
+    }
+}
+
+// The one client of a Downloader, shared by the metadata and package requests. reqwest reads
+// HTTP_PROXY, HTTPS_PROXY, ALL_PROXY and NO_PROXY from the environment unless a proxy is given
+fn http_client(pins: &[[u8; 32]], proxy_url: Option<&str>) -> Result<reqwest::Client, JavaSetupError> {
+    let mut builder = reqwest::Client::builder().user_agent(USER_AGENT);
+    if !pins.is_empty() {
+        builder = builder.use_preconfigured_tls(pinning::pinned_tls_config(pins)?);
+    }
+    if let Some(proxy_url) = proxy_url {
+        let proxy = reqwest::Proxy::all(proxy_url).map_err(|_| JavaSetupError::InvalidProxy(proxy_url.to_string()))?;
+        builder = builder.proxy(proxy.no_proxy(reqwest::NoProxy::from_env()));
+    }
+    Ok(builder.build()?)
+}
+
+impl Downloader {
+    fn query<'a>(&'a self, java_version: &'a str, image_type: &'a str) -> PackageQuery<'a> {
+        PackageQuery { java_version, image_type, os: adoptium_os(), architecture: &self.architecture }
+    }
+
+    // Rebuilds java_url after the version, image type, architecture or vendor changed
+    fn refresh_java_url(&mut self) {
+        self.java_url = self.jdk_vendor.api().metadata_url(&self.query(&self.java_version, self.image_type.as_str()));
+    }
 
-        // Finds the JDK directory containing the 'bin' folder
-        let mut jdk_dir: Option<std::path::PathBuf> = None;
-        for entry in WalkDir::new(&self.extract_path)
-            .min_depth(1)
-            .max_depth(3)
+    // The packages of a response the vendor API finds, without those of other vendors when set
+    fn packages(&self, api: &dyn VendorApi, json: &serde_json::Value, query: &PackageQuery) -> Vec<ReleaseAsset> {
+        api.packages(json, query)
             .into_iter()
-            .filter_map(|e| e.ok())
-        {
-            if entry.file_type().is_dir() && entry.path().join("bin").exists() {
-                jdk_dir = Some(entry.path().to_path_buf());
-                break;
+            .filter(|asset| match &self.vendor {
+                Some(vendor) => asset.vendor.as_deref().is_some_and(|asset_vendor| asset_vendor.eq_ignore_ascii_case(vendor)),
+                None => true,
+            })
+            .collect()
+    }
+
+    // Only accepts servers whose certificate has this SHA-256 fingerprint (hex, ':' separators allowed).
+    // Pin every host involved, including the GitHub hosts Adoptium redirects downloads to
+    pub fn pin_cert(&mut self, fingerprint: &str) -> Result<(), JavaSetupError> {
+        self.pinned_certs.push(pinning::parse_fingerprint(fingerprint)?);
+        self.client = http_client(&self.pinned_certs, self.proxy.as_deref())?;
+        Ok(())
+    }
+
+    // Sends metadata and package requests through this proxy (http:// or https://, credentials
+    // in the URL) instead of the one from the environment. Hosts in NO_PROXY still bypass it,
+    // None goes back to the environment's proxy
+    pub fn set_proxy(&mut self, proxy_url: Option<&str>) -> Result<(), JavaSetupError> {
+        let client = http_client(&self.pinned_certs, proxy_url)?;
+        self.proxy = proxy_url.map(|proxy_url| proxy_url.to_string());
+        self.client = client;
+        Ok(())
+    }
+
+    // Requests the API's info endpoint with the configured client (and its pins), failing with
+    // ApiUnreachable saying what went wrong
+    pub async fn check_connectivity(&self) -> Result<(), JavaSetupError> {
+        Ok(self.check_reachable(&self.jdk_vendor.api().status_url()).await?)
+    }
+
+    // Lists the feature releases Adoptium has GA builds for, e.g. to offer a version picker.
+    // The other vendors build the same OpenJDK releases, so the list is used for all of them
+    pub async fn available_versions(&self) -> Result<AvailableVersions, JavaSetupError> {
+        let response = self.get_metadata(ADOPTIUM_AVAILABLE_RELEASES_URL).await?.error_for_status()?;
+        let body = self.read_metadata(response).await?;
+        let json: serde_json::Value = serde_json::from_str(&body)?;
+        Ok(AvailableVersions::from_adoptium(&json)?)
+    }
+
+    async fn check_reachable(&self, url: &str) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let unreachable = |failure: ConnectivityFailure, detail: String| {
+            Box::new(JavaSetupError::ApiUnreachable { url: url.to_string(), failure, detail })
+        };
+        let response = match self.client.get(url).timeout(CONNECTIVITY_TIMEOUT).send().await {
+            Ok(response) => response,
+            Err(e) => {
+                // The innermost error has the useful text, e.g. "failed to lookup address information"
+                let mut innermost: &(dyn std::error::Error + 'static) = &e;
+                while let Some(source) = innermost.source() {
+                    innermost = source;
+                }
+                return Err(unreachable(connectivity_failure(&e), innermost.to_string()));
             }
-        }
+        };
 
-        if let Some(jdk_path) = jdk_dir {
-            copy_dir_all(&jdk_path, Path::new(&self.install_path))?;
-            println!("JDK installed to {}", self.install_path);
-        } else {
-            println!("Extracted JDK folder not found.");
+        let status = response.status();
+        if !status.is_success() {
+            return Err(unreachable(ConnectivityFailure::Http(status.as_u16()), status.to_string()));
         }
+        debug!("{} is reachable.", url);
         Ok(())
     }
-}
 
-impl EnvironmentVariableConfigurator {
-    pub unsafe fn configure(&self) -> Result<(), Box<dyn std::error::Error>> {
-        let jdk_bin_path = format!("{}\\bin", self.install_path);
-        let current_path = std::env::var("PATH").unwrap_or_default();
-        println!("Actual PATH: {}", current_path);
+    // Fetches the release metadata and resolves the JDK package to download
+    pub async fn resolve(&self) -> Result<Option<ReleaseAsset>, JavaSetupError> {
+        if let Some(source) = &self.source {
+            return Ok(source.resolve(&self.java_version).await?);
+        }
 
-        // Update the current process PATH
-        if !current_path.contains(&jdk_bin_path) {
-            let new_path = format!("{};{}", current_path, jdk_bin_path);
-            unsafe {
-            std::env::set_var("PATH", &new_path);
+        let fetched;
+        let json = match &self.metadata {
+            Some(json) => {
+                debug!("Using the supplied release metadata for Java {}", &self.java_version);
+                json
+            }
+            None => {
+                debug!("Download URL for Java {}: {}", &self.java_version, &self.java_url);
+                let response = self.get_metadata(&self.java_url).await?;
+                let body = self.read_metadata(response).await?;
+                debug!("JSON response: {}", &body);
+                fetched = serde_json::from_str(&body)?;
+                &fetched
+            }
+        };
+
+        // Extracts the JDK download link from the JSON response
+        let mut packages = self.packages(self.jdk_vendor.api(), json, &self.query(&self.java_version, self.image_type.as_str()));
+        let asset = match &self.asset_name {
+            Some(asset_name) => packages.into_iter()
+                .find(|asset| &asset.name == asset_name)
+                .ok_or_else(|| JavaSetupError::AssetNotFound(asset_name.clone()))?,
+            None if !packages.is_empty() => packages.remove(0),
+            None if self.metadata.is_some() => return Ok(None),
+            None => return self.missing_architecture().await.map(|_| None),
+        };
+        Ok(Some(asset))
+    }
+
+    // Explains an empty answer with ArchitectureUnavailable when the release exists for other
+    // architectures, otherwise lets the caller report the missing package
+    async fn missing_architecture(&self) -> Result<(), JavaSetupError> {
+        let api = self.jdk_vendor.api();
+        let Some(url) = api.architectures_url(&self.query(&self.java_version, self.image_type.as_str())) else {
+            return Ok(());
+        };
+        let available = async {
+            let response = self.get_metadata(&url).await?;
+            if !response.status().is_success() {
+                return Ok(Vec::new());
+            }
+            let json: serde_json::Value = serde_json::from_str(&self.read_metadata(response).await?)?;
+            Ok::<_, Box<dyn std::error::Error + Send + Sync>>(api.architectures(&json))
+        };
+        match available.await {
+            Ok(available) if !available.is_empty() && !available.contains(&self.architecture) => {
+                Err(JavaSetupError::ArchitectureUnavailable {
+                    architecture: self.architecture.clone(),
+                    java_version: self.java_version.clone(),
+                    available,
+                })
+            }
+            Ok(_) => Ok(()),
+            Err(e) => {
+                warn!("Could not list the available architectures: {}", e);
+                Ok(())
             }
-            println!("Updated PATH with JDK bin.");
-        } else {
-            println!("The PATH already contains the JDK bin.");
         }
+    }
 
-        // Generates and runs the PowerShell script to update the user's PATH
-        let script_content = format!(
-            r#"
-$jdkPath = "{jdk_bin_path}"
-$userPath = [Environment]::GetEnvironmentVariable("PATH", "User")
-if ($userPath -notlike "*$jdkPath*") {{
-    $newPath = "$userPath;$jdkPath"
-    [Environment]::SetEnvironmentVariable("PATH", $newPath, "User")
-    Write-Host "Updated user's PATH."
-}} else {{
-    Write-Host "PATH already contains the JDK."
-}}
-"#);
+    // Checks whether a binary exists for the configured version/os/arch without downloading it
+    pub async fn is_available(&self) -> Result<bool, JavaSetupError> {
+        if let Some(source) = &self.source {
+            return Ok(source.resolve(&self.java_version).await?.is_some());
+        }
+        let json: serde_json::Value = match &self.metadata {
+            Some(json) => json.clone(),
+            None => {
+                let response = self.get_metadata(&self.java_url).await?;
+                if !response.status().is_success() {
+                    return Ok(false);
+                }
+                serde_json::from_str(&self.read_metadata(response).await?)?
+            }
+        };
 
-        // Get main disk
-        let main_disk = std::env::var("SystemDrive").unwrap_or_else(|_| "C:".into());
-        println!("Main disk: {}", main_disk);
+        // Any package with a download link will do
+        let query = self.query(&self.java_version, self.image_type.as_str());
+        Ok(!self.packages(self.jdk_vendor.api(), &json, &query).is_empty())
+    }
+
+    // Resolves the latest GA JDK of a feature release, regardless of the configured version
+    pub async fn resolve_latest(&self, feature_version: u32) -> Result<Option<ReleaseAsset>, JavaSetupError> {
+        let feature_version = feature_version.to_string();
+        let query = self.query(&feature_version, self.image_type.as_str());
+        let api = self.jdk_vendor.api();
+        let response = self.get_metadata(&api.metadata_url(&query)).await?;
+        if !response.status().is_success() {
+            return Ok(None);
+        }
+        let json: serde_json::Value = serde_json::from_str(&self.read_metadata(response).await?)?;
+        Ok(self.packages(api, &json, &query).into_iter().next())
+    }
+
+    // When download_path is a directory the archive keeps the file name given by the server
+    pub async fn download(&self, asset: &ReleaseAsset) -> Result<DownloadedFile, JavaSetupError> {
+        debug!("JDK download link: {}", &asset.link);
+        let mut redownloads = 0;
+        let downloaded = loop {
+            let downloaded = match &self.source {
+                Some(source) => self.fetch_from_source(source.as_ref(), asset).await,
+                None => self.download_asset(asset, Path::new(&self.download_path)).await,
+            };
+            match downloaded {
+                Ok(downloaded) => break downloaded,
+                Err(e) => {
+                    // The corrupt file is already removed by verify_checksum
+                    let Some(JavaSetupError::ChecksumMismatch { expected, actual }) = e.downcast_ref::<JavaSetupError>() else {
+                        return Err(e.into());
+                    };
+                    warn!("Attempt {} for {}: expected {}, computed {}", redownloads + 1, &asset.name, expected, actual);
+                    if redownloads == self.checksum_redownloads {
+                        return Err(e.into());
+                    }
+                    redownloads += 1;
+                    warn!("Downloading {} again ({}/{})...", &asset.name, redownloads, self.checksum_redownloads);
+                }
+            }
+        };
+        info!("JDK downloaded to {}", downloaded.path.display());
+        Ok(downloaded)
+    }
 
-        // Get %temp% dir
-        let temp_dir = std::env::var("TEMP").unwrap_or_else(|_| format!("{}\\Temp", main_disk).into());
-        let script_path = format!("{}\\add_jdk_to_path.ps1", temp_dir);
-        println!("Creating PowerShell script at: {}", &script_path);
-        fs::write(&script_path, script_content)?;
+    // Resolves a supplementary package for the configured version, if Adoptium offers it. The
+    // other vendors publish none, so these always come from Adoptium
+    pub async fn resolve_supplementary(
+        &self,
+        package: SupplementaryPackage,
+    ) -> Result<Option<ReleaseAsset>, JavaSetupError> {
+        let query = self.query(&self.java_version, package.image_type());
+        let api = Vendor::Adoptium.api();
+        let url = api.metadata_url(&query);
+        debug!("Download URL for {}: {}", package.image_type(), &url);
+        let response = self.get_metadata(&url).await?;
 
-        let status = std::process::Command::new("powershell")
-            .args(&["-ExecutionPolicy", "Bypass", "-File", &script_path])
-            .status()?;
+        // Adoptium answers 404 when no release matches the requested image type
+        if !response.status().is_success() {
+            return Ok(None);
+        }
+        let json: serde_json::Value = serde_json::from_str(&self.read_metadata(response).await?)?;
+        Ok(self.packages(api, &json, &query).into_iter().next())
+    }
 
-        if status.success() {
-            println!("Powershell script executed correctly.");
+    // Has the source write the archive where download_asset would have put it
+    async fn fetch_from_source(&self, source: &dyn JdkSource, asset: &ReleaseAsset) -> Result<DownloadedFile, Box<dyn std::error::Error + Send + Sync>> {
+        let destination = Path::new(&self.download_path);
+        let path = if is_dir_path(destination) {
+            fs::create_dir_all(destination)?;
+            destination.join(safe_file_name(&asset.name).unwrap_or_else(|| "java_download".to_string()))
         } else {
-            println!("There was an error executing the PowerShell script.");
+            destination.to_path_buf()
+        };
+        self.cleanup.track(&path);
+        let mut attempt = 1;
+        while let Err(e) = source.fetch(asset, &path, &*self.progress).await {
+            if !self.wait_to_retry(e.as_ref(), &mut attempt).await {
+                return Err(e);
+            }
         }
 
-        Ok(())
+        // The source wrote the file itself, so it is hashed from disk
+        let checksum_verified = self.verify_checksum(asset, &path, None)?;
+        Ok(DownloadedFile { path, checksum_verified })
     }
-}
 
-impl JavaSetup {
-    pub fn new(java_version: &str, download_path: &str, extract_path: &str, install_path: &str) -> Self {
-        let java_url = format!(
-            "https://api.adoptium.net/v3/assets/feature_releases/{}/ga?architecture=x64&os=windows&image_type=jdk",
-            java_version
-        );
-        JavaSetup {
-            downloader: Downloader {
-                java_version: java_version.to_string(),
-                download_path: download_path.to_string(),
-                java_url,
-            },
-            extractor: Extractor {
-                download_path: download_path.to_string(),
-                extract_path: extract_path.to_string(),
-            },
-            installer: Installer {
-                extract_path: extract_path.to_string(),
-                install_path: install_path.to_string(),
-            },
-            env_configurator: EnvironmentVariableConfigurator {
-                install_path: install_path.to_string(),
-            },
+    async fn download_asset(&self, asset: &ReleaseAsset, destination: &Path) -> Result<DownloadedFile, Box<dyn std::error::Error + Send + Sync>> {
+        use sha2::Digest;
+
+        let mut attempt = 1;
+        let (mut response, _) = self.request_package(&asset.link, 0, &mut attempt).await?;
+
+        // Redirects (e.g. to GitHub releases) often carry the real file name in Content-Disposition
+        let path = if is_dir_path(destination) {
+            let file_name = response
+                .headers()
+                .get(reqwest::header::CONTENT_DISPOSITION)
+                .and_then(|value| value.to_str().ok())
+                .and_then(content_disposition_filename)
+                .or_else(|| safe_file_name(&asset.name))
+                .or_else(|| response.url().path_segments().and_then(|mut segments| segments.next_back()).and_then(safe_file_name))
+                .unwrap_or_else(|| "java_download".to_string());
+            fs::create_dir_all(destination)?;
+            destination.join(file_name)
+        } else {
+            destination.to_path_buf()
+        };
+        self.cleanup.track(&path);
+        let mut file = std::fs::File::create(&path)?;
+        let mut hasher = sha2::Sha256::new();
+        let started = std::time::Instant::now();
+        let mut downloaded: u64 = 0;
+        let total = response.content_length();
+
+        // Streams the body to disk chunk by chunk instead of buffering it in memory, hashing
+        // exactly the bytes written so the file isn't read back for the checksum
+        loop {
+            let transferred = async {
+                while let Some(chunk) = self.unless_cancelled(response.chunk()).await?? {
+                    file.write_all(&chunk)?;
+                    hasher.update(&chunk);
+                    downloaded += chunk.len() as u64;
+                    self.progress.bytes_progress(downloaded, total);
+                    self.throttle(started, downloaded).await;
+                }
+                Ok::<_, Box<dyn std::error::Error + Send + Sync>>(())
+            }
+            .await;
+            let Err(e) = transferred else {
+                break;
+            };
+            if !self.wait_to_retry(e.as_ref(), &mut attempt).await {
+                return Err(e);
+            }
+            // A dropped connection continues with the missing bytes when the server supports ranges
+            let (next, resumed) = self.request_package(&asset.link, downloaded, &mut attempt).await?;
+            if resumed {
+                info!("Resuming the download of {} at {} bytes", &asset.name, downloaded);
+            } else {
+                info!("The server can't resume {}, downloading it again.", &asset.name);
+                file.set_len(0)?;
+                std::io::Seek::rewind(&mut file)?;
+                hasher = sha2::Sha256::new();
+                downloaded = 0;
+            }
+            response = next;
         }
+        drop(file);
+
+        let actual = format!("{:x}", hasher.finalize());
+        let checksum_verified = self.verify_checksum(asset, &path, Some(actual))?;
+        Ok(DownloadedFile { path, checksum_verified })
     }
 
-    pub async fn setup(&mut self) -> Result<(), Box<dyn std::error::Error>> {
-        // Format download_path to remove the file name and keep only the directory
-        let download_dir = Path::new(&self.downloader.download_path)
-            .parent()
-            .unwrap_or(Path::new("."))
-            .to_str()
-            .unwrap_or(".");
+    // Sends the body of the package to chunks as it arrives and returns its SHA-256
+    pub async fn stream_asset(
+        &self,
+        asset: &ReleaseAsset,
+        chunks: tokio::sync::mpsc::Sender<bytes::Bytes>,
+    ) -> Result<String, JavaSetupError> {
+        use sha2::Digest;
 
-        // If the download directory does not exist, create it
-        if !Path::new(download_dir).exists() {
-            fs::create_dir_all(download_dir)?;
+        let mut attempt = 1;
+        let (mut response, _) = self.request_package(&asset.link, 0, &mut attempt).await?;
+        let mut hasher = sha2::Sha256::new();
+        let started = std::time::Instant::now();
+        let mut downloaded: u64 = 0;
+        let total = response.content_length();
+
+        loop {
+            let chunk = match self.unless_cancelled(response.chunk()).await? {
+                Ok(Some(chunk)) => chunk,
+                Ok(None) => break,
+                Err(e) => {
+                    if !self.wait_to_retry(&e, &mut attempt).await {
+                        return Err(e.into());
+                    }
+                    // The unpacker already has the first bytes, so only a resumed body can continue
+                    let (next, resumed) = self.request_package(&asset.link, downloaded, &mut attempt).await?;
+                    if !resumed && downloaded > 0 {
+                        return Err(e.into());
+                    }
+                    info!("Resuming the download of {} at {} bytes", &asset.name, downloaded);
+                    response = next;
+                    continue;
+                }
+            };
+            hasher.update(&chunk);
+            downloaded += chunk.len() as u64;
+            self.progress.bytes_progress(downloaded, total);
+            if chunks.send(chunk).await.is_err() {
+                return Err(std::io::Error::new(std::io::ErrorKind::BrokenPipe, "the unpacker stopped reading").into());
+            }
+            self.throttle(started, downloaded).await;
         }
+        Ok(format!("{:x}", hasher.finalize()))
+    }
 
-        println!("Starting download...");
-        self.downloader.download().await?;
-        println!("Extracting...");
-        self.extractor.extract()?;
-        println!("Installing...");
-        self.installer.install()?;
-        println!("Configuring environment variables...");
-        unsafe {
-            self.env_configurator.configure()?;
+    pub(crate) fn retry_decision(&self, error: &(dyn std::error::Error + Send + Sync + 'static), attempt: u32) -> RetryDecision {
+        // A cancelled setup stops at the next failure, whatever the policy says
+        if self.cancel.is_cancelled() {
+            return RetryDecision::Abort;
+        }
+        match &self.retry_policy {
+            Some(policy) => policy(error, attempt),
+            None => backoff_retry_policy(error, attempt, self.retry_attempts, self.retry_base_delay),
+        }
+    }
+
+    // Sleeps before the next attempt and counts it when the retry policy allows one
+    async fn wait_to_retry(&self, error: &(dyn std::error::Error + Send + Sync + 'static), attempt: &mut u32) -> bool {
+        match self.retry_decision(error, *attempt) {
+            RetryDecision::Retry(delay) => {
+                warn!("Request failed ({}), retrying in {:?}...", error, delay);
+                if self.unless_cancelled(tokio::time::sleep(delay)).await.is_err() {
+                    return false;
+                }
+                *attempt += 1;
+                true
+            }
+            RetryDecision::Abort => false,
         }
-        println!("Done! Deleting temporary files...");
+    }
 
-        if !Path::new(download_dir).exists() {
-            println!("No temporary files to delete.");
-            return Ok(());
+    // Runs future unless the token is cancelled first, a stalled request doesn't hold up a cancel
+    async fn unless_cancelled<T>(&self, future: impl std::future::Future<Output = T>) -> Result<T, JavaSetupError> {
+        tokio::select! {
+            biased;
+            _ = self.cancel.cancelled() => Err(JavaSetupError::Cancelled),
+            output = future => Ok(output),
         }
+    }
 
-        fs::remove_dir_all(download_dir)?;
-        println!("Temporary files deleted.");
+    // Requests API metadata, retrying failed connections and 5xx answers. Other answers are
+    // returned as they are, Adoptium uses 404 for a release that doesn't exist
+    async fn get_metadata(&self, url: &str) -> Result<reqwest::Response, Box<dyn std::error::Error + Send + Sync>> {
+        let mut attempt = 1;
+        loop {
+            let error: Box<dyn std::error::Error + Send + Sync> = match self.unless_cancelled(self.client.get(url).send()).await? {
+                Ok(response) if response.status() == reqwest::StatusCode::TOO_MANY_REQUESTS => Box::new(rate_limited(&response)),
+                Ok(response) if !response.status().is_server_error() => return Ok(response),
+                Ok(response) => match response.error_for_status() {
+                    Ok(response) => return Ok(response),
+                    Err(e) => Box::new(e),
+                },
+                Err(e) => Box::new(e),
+            };
+            if !self.wait_to_retry(error.as_ref(), &mut attempt).await {
+                return Err(error);
+            }
+        }
+    }
 
-        Ok(())
+    // Requests the package from byte offset on, retrying failed connections and 5xx answers.
+    // Returns whether the server resumed at offset, with false the body starts over
+    async fn request_package(&self, link: &str, offset: u64, attempt: &mut u32) -> Result<(reqwest::Response, bool), Box<dyn std::error::Error + Send + Sync>> {
+        loop {
+            let mut request = self.client.get(link);
+            if offset > 0 {
+                request = request.header(reqwest::header::RANGE, format!("bytes={}-", offset));
+            }
+            let error: Box<dyn std::error::Error + Send + Sync> = match self.unless_cancelled(request.send()).await? {
+                Ok(response) if response.status() == reqwest::StatusCode::TOO_MANY_REQUESTS => Box::new(rate_limited(&response)),
+                Ok(response) => match response.error_for_status() {
+                    Ok(response) => {
+                        let resumed = offset > 0 && response.status() == reqwest::StatusCode::PARTIAL_CONTENT;
+                        return Ok((response, resumed));
+                    }
+                    Err(e) => Box::new(e),
+                },
+                Err(e) => Box::new(e),
+            };
+            if !self.wait_to_retry(error.as_ref(), attempt).await {
+                return Err(error);
+            }
+        }
+    }
+
+    // Reads a metadata body, refusing anything larger than max_metadata_bytes
+    async fn read_metadata(&self, mut response: reqwest::Response) -> Result<String, Box<dyn std::error::Error + Send + Sync>> {
+        let too_large = |response: &reqwest::Response| {
+            Box::new(JavaSetupError::ResponseTooLarge {
+                url: response.url().to_string(),
+                limit: self.max_metadata_bytes,
+            })
+        };
+        if response.content_length().is_some_and(|length| length > self.max_metadata_bytes) {
+            return Err(too_large(&response));
+        }
+
+        // Content-Length can be missing or wrong, so the limit is enforced while reading too
+        let mut body = Vec::new();
+        while let Some(chunk) = self.unless_cancelled(response.chunk()).await?? {
+            if (body.len() + chunk.len()) as u64 > self.max_metadata_bytes {
+                return Err(too_large(&response));
+            }
+            body.extend_from_slice(&chunk);
+        }
+        Ok(String::from_utf8_lossy(&body).into_owned())
+    }
+
+    // Sleeps until the average rate falls back under the configured cap
+    async fn throttle(&self, started: std::time::Instant, downloaded: u64) {
+        if let Some(max_bytes_per_sec) = self.max_bytes_per_sec {
+            let target = std::time::Duration::from_secs_f64(downloaded as f64 / max_bytes_per_sec as f64);
+            let elapsed = started.elapsed();
+            if target > elapsed {
+                // The next chunk read reports a cancel that cut the pause short
+                let _ = self.unless_cancelled(tokio::time::sleep(target - elapsed)).await;
+            }
+        }
+    }
+
+    // Applies the checksum policy to a downloaded file, removing it on mismatch. actual is the
+    // SHA-256 computed while writing the file, None hashes the file
+    fn verify_checksum(&self, asset: &ReleaseAsset, path: &Path, actual: Option<String>) -> Result<bool, Box<dyn std::error::Error + Send + Sync>> {
+        let expected = match self.expected_checksum(asset) {
+            Ok(Some(expected)) => expected,
+            Ok(None) => return Ok(false),
+            Err(e) => {
+                fs::remove_file(path)?;
+                return Err(e);
+            }
+        };
+
+        let actual = match actual {
+            Some(actual) => actual,
+            None => integrity::sha256_file(path)?,
+        };
+        if let Err(e) = Self::compare_checksum(asset, expected, actual) {
+            fs::remove_file(path)?;
+            return Err(e);
+        }
+        Ok(true)
     }
-}
\ No newline at end of file
+
+    // The checksum to verify against under the policy, None when verification is skipped
+    fn expected_checksum<'a>(&self, asset: &'a ReleaseAsset) -> Result<Option<&'a str>, Box<dyn std::error::Error + Send + Sync>> {
+        match (&asset.checksum, self.checksum_policy) {
+            (_, ChecksumPolicy::Skip) => Ok(None),
+            (Some(expected), _) => Ok(Some(expected)),
+            (None, ChecksumPolicy::Require) => Err(Box::new(JavaSetupError::ChecksumMissing(asset.name.clone()))),
+            (None, ChecksumPolicy::VerifyIfPresent) => {
+                info!("No checksum published for {}, skipping verification.", &asset.name);
+                Ok(None)
+            }
+        }
+    }
+
+    fn compare_checksum(asset: &ReleaseAsset, expected: &str, actual: String) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        if !actual.eq_ignore_ascii_case(expected) {
+            return Err(Box::new(JavaSetupError::ChecksumMismatch {
+                expected: expected.to_string(),
+                actual,
+            }));
+        }
+        info!("Checksum verified for {}", &asset.name);
+        Ok(())
+    }
+}
+
+impl Extractor {
+    pub fn extract(&self) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        debug!("Extracting from {} to {}", &self.download_path, &self.extract_path);
+        let file_name = self.file_name();
+        let handler = self
+            .registry
+            .find(&file_name)
+            .ok_or_else(|| Box::new(JavaSetupError::UnsupportedArchive(file_name.clone())) as Box<dyn std::error::Error + Send + Sync>)?;
+
+        let mut file = archive::CancellableReader::new(std::io::BufReader::new(self.open_download()?), self.cancel.clone());
+        handler
+            .extract(&mut file, Path::new(&self.extract_path), self.duplicate_policy, &*self.progress)
+            .map_err(|e| self.cancelled_or(e))?;
+        info!("JDK extracted to {}", self.extract_path);
+        Ok(())
+    }
+
+    // Handlers wrap the reader's errors in their own, so a cancel is recognized by the token
+    fn cancelled_or(&self, error: Box<dyn std::error::Error + Send + Sync>) -> Box<dyn std::error::Error + Send + Sync> {
+        if self.cancel.is_cancelled() { Box::new(JavaSetupError::Cancelled) } else { error }
+    }
+
+    // Extracts zip archives entry by entry, yielding to the runtime in between so a
+    // single-threaded runtime keeps running other tasks. Other formats and custom zip
+    // handlers can't be interrupted and run on the blocking thread pool instead
+    pub async fn extract_async(&self) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let file_name = self.file_name();
+        if !self.registry.is_builtin_zip(&file_name) {
+            let extractor = Extractor {
+                download_path: self.download_path.clone(),
+                extract_path: self.extract_path.clone(),
+                registry: self.registry.clone(),
+                duplicate_policy: self.duplicate_policy,
+                progress: self.progress.clone(),
+                cancel: self.cancel.clone(),
+            };
+            return tokio::task::spawn_blocking(move || extractor.extract()).await?;
+        }
+
+        debug!("Extracting from {} to {}", &self.download_path, &self.extract_path);
+        let mut file = std::io::BufReader::new(self.open_download()?);
+        let mut archive = zip::ZipArchive::new(&mut file)?;
+        let mut duplicates = archive::DuplicateTracker::new(self.duplicate_policy);
+        let total = archive.len() as u64;
+        for i in 0..archive.len() {
+            if self.cancel.is_cancelled() {
+                return Err(Box::new(JavaSetupError::Cancelled));
+            }
+            archive::extract_zip_entry(&mut archive, i, Path::new(&self.extract_path), &mut duplicates)?;
+            self.progress.entry_progress(i as u64 + 1, Some(total));
+            tokio::task::yield_now().await;
+        }
+        info!("JDK extracted to {}", self.extract_path);
+        Ok(())
+    }
+
+    // Opens the downloaded archive, waiting out an antivirus scan that locks it right after the download
+    fn open_download(&self) -> Result<std::fs::File, Box<dyn std::error::Error + Send + Sync>> {
+        let mut attempt = 1;
+        loop {
+            match std::fs::File::open(&self.download_path) {
+                Ok(file) => return Ok(file),
+                Err(e) if is_quarantine_error(&e) && attempt < QUARANTINE_OPEN_ATTEMPTS => {
+                    warn!("{} is locked ({}), retrying...", &self.download_path, e);
+                    std::thread::sleep(QUARANTINE_RETRY_DELAY * attempt);
+                    attempt += 1;
+                }
+                Err(e) => return Err(self.open_error(e)),
+            }
+        }
+    }
+
+    fn open_error(&self, error: std::io::Error) -> Box<dyn std::error::Error + Send + Sync> {
+        if is_quarantine_error(&error) {
+            Box::new(JavaSetupError::FileQuarantined(self.download_path.clone()))
+        } else {
+            Box::new(error)
+        }
+    }
+
+    fn file_name(&self) -> String {
+        Path::new(&self.download_path)
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default()
+    }
+}
+
+// Counts of what copy_dir_all did
+#[derive(Debug, Clone, Default)]
+struct CopyStats {
+    files_copied: usize,
+    files_skipped: usize,
+    // Files that only copied after one or more retries, relative to the JDK root
+    files_retried: Vec<PathBuf>,
+    // install_path was linked to the extracted JDK instead of copied
+    linked: bool,
+}
+
+const COPY_ATTEMPTS: u32 = 4;
+
+// How often a freshly downloaded archive is opened before it is reported as quarantined
+const QUARANTINE_OPEN_ATTEMPTS: u32 = 5;
+const QUARANTINE_RETRY_DELAY: Duration = Duration::from_millis(500);
+
+// On Windows, access denied or sharing violations right after a download are antivirus scanners
+// holding the file, a missing file is one they already moved to quarantine. Elsewhere these are
+// plain errors and fail right away
+fn is_quarantine_error(error: &std::io::Error) -> bool {
+    use std::io::ErrorKind;
+    cfg!(windows)
+        && (matches!(error.kind(), ErrorKind::PermissionDenied | ErrorKind::NotFound)
+            || matches!(error.raw_os_error(), Some(5) | Some(32) | Some(33)))
+}
+
+// Errors worth retrying: files briefly locked by antivirus scanners or network drives
+fn is_transient_io_error(error: &std::io::Error) -> bool {
+    use std::io::ErrorKind;
+    if matches!(
+        error.kind(),
+        ErrorKind::Interrupted | ErrorKind::WouldBlock | ErrorKind::TimedOut | ErrorKind::ResourceBusy
+    ) {
+        return true;
+    }
+    // ERROR_SHARING_VIOLATION and ERROR_LOCK_VIOLATION. ERROR_ACCESS_DENIED is left out, a target
+    // that needs administrator rights goes straight to NeedsElevation
+    cfg!(windows) && matches!(error.raw_os_error(), Some(32) | Some(33))
+}
+
+// Copies a single file, retrying transient errors with a short backoff.
+// Returns whether a retry was needed.
+fn copy_file_with_retry(src: &Path, dst: &Path) -> std::io::Result<bool> {
+    let mut attempt = 1;
+    loop {
+        match fs::copy(src, dst) {
+            Ok(_) => return Ok(attempt > 1),
+            Err(e) if attempt < COPY_ATTEMPTS && is_transient_io_error(&e) => {
+                warn!("Copying {} failed ({}), retrying...", src.display(), e);
+                std::thread::sleep(std::time::Duration::from_millis(100 << attempt));
+                attempt += 1;
+            }
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+// Decides from a path relative to the JDK root whether it gets installed
+type CopyFilter = Box<dyn Fn(&Path) -> bool + Send + Sync>;
+
+// Recursively copies the files and directories from src to dst accepted by include.
+// Rejecting a directory skips everything below it.
+fn copy_dir_all(
+    src: &Path,
+    dst: &Path,
+    include: &dyn Fn(&Path) -> bool,
+    progress: &dyn ProgressReporter,
+) -> std::io::Result<CopyStats> {
+    let mut stats = CopyStats::default();
+    let mut skipped_dirs: Vec<PathBuf> = Vec::new();
+    fs::create_dir_all(dst)?;
+    // Directories before files, then by name, so two installs of the same archive copy in the same order
+    let walker = WalkDir::new(src).sort_by(|a, b| {
+        a.file_type()
+            .is_file()
+            .cmp(&b.file_type().is_file())
+            .then_with(|| a.file_name().cmp(b.file_name()))
+    });
+    for entry in walker {
+        let entry = entry?;
+        let rel_path = entry.path().strip_prefix(src).unwrap();
+        let skipped = skipped_dirs.iter().any(|dir| rel_path.starts_with(dir))
+            || (!rel_path.as_os_str().is_empty() && !include(rel_path));
+        if skipped {
+            if entry.file_type().is_dir() {
+                skipped_dirs.push(rel_path.to_path_buf());
+            } else {
+                stats.files_skipped += 1;
+            }
+            continue;
+        }
+
+        let dest_path = dst.join(rel_path);
+        if entry.file_type().is_dir() {
+            fs::create_dir_all(&dest_path)?;
+        } else {
+            if copy_file_with_retry(entry.path(), &dest_path)? {
+                stats.files_retried.push(rel_path.to_path_buf());
+            }
+            stats.files_copied += 1;
+            progress.file_copied(rel_path);
+        }
+    }
+    Ok(stats)
+}
+
+// Finds the JDK directory containing the 'bin' folder, the extract path itself for archives
+// without a top-level folder (as some JRE packages are)
+fn find_jdk_root(extract_path: &Path) -> Option<PathBuf> {
+    WalkDir::new(extract_path)
+        .max_depth(3)
+        .into_iter()
+        .filter_map(|e| e.ok())
+        .find(|entry| entry.file_type().is_dir() && entry.path().join("bin").exists())
+        .map(|entry| entry.path().to_path_buf())
+}
+
+impl Installer {
+    // The explicit subpath when one is set, otherwise the detected JDK root
+    fn jdk_root(&self, extract_path: &Path) -> Option<PathBuf> {
+        let Some(subpath) = &self.jdk_root_subpath else {
+            return find_jdk_root(extract_path);
+        };
+        // The subpath must stay inside the extracted tree
+        if !subpath.components().all(|component| matches!(component, std::path::Component::Normal(_) | std::path::Component::CurDir)) {
+            warn!("Ignoring JDK root {}, it must be relative to the extracted files.", subpath.display());
+            return None;
+        }
+        let jdk_root = extract_path.join(subpath);
+        if jdk_root.is_dir() {
+            Some(jdk_root)
+        } else {
+            warn!("JDK root {} does not exist.", jdk_root.display());
+            None
+        }
+    }
+
+    // Fails with UnexpectedArchiveLayout when the JDK root misses an expected entry,
+    // before anything of the previous install is touched
+    fn check_layout(&self, jdk_root: &Path) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let defaults = match self.image_type {
+            ImageType::Jdk => DEFAULT_JDK_ENTRIES,
+            ImageType::Jre => DEFAULT_JRE_ENTRIES,
+        };
+        let expected: Vec<&str> = match &self.expected_entries {
+            Some(entries) => entries.iter().map(String::as_str).collect(),
+            None => defaults.to_vec(),
+        };
+        let missing: Vec<String> = expected
+            .into_iter()
+            .filter(|entry| !jdk_root.join(entry).exists())
+            .map(str::to_string)
+            .collect();
+        if !missing.is_empty() {
+            return Err(Box::new(JavaSetupError::UnexpectedArchiveLayout {
+                root: jdk_root.display().to_string(),
+                missing,
+            }));
+        }
+        Ok(())
+    }
+
+    // Picks the directory the JDK goes to according to the existing install policy
+    pub fn target_path(&self, version: &str) -> Result<PathBuf, Box<dyn std::error::Error + Send + Sync>> {
+        let install_path = PathBuf::from(&self.install_path);
+        if !install_path.exists() {
+            return Ok(install_path);
+        }
+
+        match self.existing_policy {
+            ExistingInstallPolicy::Replace => Ok(install_path),
+            ExistingInstallPolicy::FailIfExists => Err(Box::new(JavaSetupError::InstallExists(self.install_path.clone()))),
+            ExistingInstallPolicy::KeepVersioned => {
+                // A previous install of the same version is the only one replaced
+                let version = version.trim_start_matches("jdk-").trim_start_matches("jdk");
+                Ok(PathBuf::from(format!("{}-{}", self.install_path, version)))
+            }
+        }
+    }
+
+    // allow_link is false when extract_path won't outlive the setup, so a link would dangle
+    pub fn install(&mut self, target: &Path, allow_link: bool) -> Result<CopyStats, Box<dyn std::error::Error + Send + Sync>> {
+        debug!("Installing from {} to {}", &self.extract_path, target.display());
+        let jdk_path = self
+            .jdk_root(Path::new(&self.extract_path))
+            .ok_or(JavaSetupError::JdkDirNotFound)?;
+        self.check_layout(&jdk_path)?;
+        remove_install_dir(target)?;
+
+        let mut stats = CopyStats::default();
+        if self.mode == InstallMode::Link && allow_link && link_dir(&jdk_path, target) {
+            info!("JDK linked from {} to {}", target.display(), jdk_path.display());
+            stats.linked = true;
+        } else {
+            // Copies everything unless the caller provided a filter
+            let include: &dyn Fn(&Path) -> bool = match &self.copy_filter {
+                Some(filter) => filter.as_ref(),
+                None => &|_| true,
+            };
+            stats = copy_dir_all(&jdk_path, target, include, &*self.progress)?;
+            info!("JDK installed to {} ({} files skipped)", target.display(), stats.files_skipped);
+        }
+        Ok(stats)
+    }
+}
+
+// Removes a previous install, deleting only the link itself when install_path is a symlink
+fn remove_install_dir(target: &Path) -> std::io::Result<()> {
+    match target.symlink_metadata() {
+        // Directory symlinks are directories to Windows but files to Unix
+        Ok(metadata) if metadata.is_symlink() => {
+            if cfg!(windows) {
+                fs::remove_dir(target)
+            } else {
+                fs::remove_file(target)
+            }
+        }
+        Ok(_) => fs::remove_dir_all(target),
+        Err(_) => Ok(()),
+    }
+}
+
+// path with suffix appended to its file name, e.g. jdk-17 -> jdk-17.partial
+fn sibling_path(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(suffix);
+    path.with_file_name(name)
+}
+
+// Replaces target with staged by renames only. The previous install is moved aside first and
+// put back if staged can't take its place
+fn swap_in(staged: &Path, target: &Path) -> std::io::Result<()> {
+    let previous = sibling_path(target, ".previous");
+    remove_install_dir(&previous)?;
+    let had_previous = target.symlink_metadata().is_ok();
+    if had_previous {
+        fs::rename(target, &previous)?;
+    }
+    if let Err(e) = fs::rename(staged, target) {
+        if had_previous {
+            fs::rename(&previous, target)?;
+        }
+        return Err(e);
+    }
+    if had_previous {
+        remove_install_dir(&previous)?;
+    }
+    Ok(())
+}
+
+// Links target to jdk_path when both are on the same volume, false if the caller has to copy instead
+fn link_dir(jdk_path: &Path, target: &Path) -> bool {
+    let parent = target.parent().filter(|parent| !parent.as_os_str().is_empty()).unwrap_or(Path::new("."));
+    if let Err(e) = fs::create_dir_all(parent) {
+        warn!("Could not create {} ({}), copying instead.", parent.display(), e);
+        return false;
+    }
+    if !same_volume(jdk_path, parent) {
+        warn!("{} is on another volume, copying instead of linking.", jdk_path.display());
+        return false;
+    }
+    // The link must not depend on the working directory
+    let jdk_path = match fs::canonicalize(jdk_path) {
+        Ok(jdk_path) => jdk_path,
+        Err(_) => return false,
+    };
+
+    #[cfg(windows)]
+    let result = std::os::windows::fs::symlink_dir(&jdk_path, target);
+    #[cfg(unix)]
+    let result = std::os::unix::fs::symlink(&jdk_path, target);
+    #[cfg(not(any(windows, unix)))]
+    let result: std::io::Result<()> = Err(std::io::ErrorKind::Unsupported.into());
+
+    match result {
+        Ok(()) => true,
+        // Windows needs Developer Mode or the symlink privilege
+        Err(e) => {
+            warn!("Could not link {} ({}), copying instead.", target.display(), e);
+            false
+        }
+    }
+}
+
+// Atomically (on Unix) repoints link at target by renaming a fresh link over it
+fn point_link(link: &Path, target: &Path) -> std::io::Result<()> {
+    let target = fs::canonicalize(target)?;
+    if let Some(parent) = link.parent().filter(|parent| !parent.as_os_str().is_empty()) {
+        fs::create_dir_all(parent)?;
+    }
+    let mut temp_name = link.file_name().unwrap_or_default().to_os_string();
+    temp_name.push(".new");
+    let temp_link = link.with_file_name(temp_name);
+    remove_install_dir(&temp_link)?;
+
+    #[cfg(windows)]
+    std::os::windows::fs::symlink_dir(&target, &temp_link)?;
+    #[cfg(unix)]
+    std::os::unix::fs::symlink(&target, &temp_link)?;
+    #[cfg(not(any(windows, unix)))]
+    return Err(std::io::ErrorKind::Unsupported.into());
+
+    // Windows can't rename over an existing directory link
+    if cfg!(windows) {
+        remove_install_dir(link)?;
+    }
+    fs::rename(&temp_link, link)
+}
+
+fn same_dir(a: &Path, b: &Path) -> bool {
+    matches!((fs::canonicalize(a), fs::canonicalize(b)), (Ok(a), Ok(b)) if a == b)
+}
+
+fn same_volume(a: &Path, b: &Path) -> bool {
+    #[cfg(windows)]
+    {
+        let prefix = |path: &Path| {
+            fs::canonicalize(path)
+                .ok()
+                .and_then(|path| path.components().next().map(|prefix| prefix.as_os_str().to_ascii_lowercase()))
+        };
+        matches!((prefix(a), prefix(b)), (Some(a), Some(b)) if a == b)
+    }
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::MetadataExt;
+        matches!((fs::metadata(a), fs::metadata(b)), (Ok(a), Ok(b)) if a.dev() == b.dev())
+    }
+    #[cfg(not(any(windows, unix)))]
+    {
+        let _ = (a, b);
+        false
+    }
+}
+
+// Compares two PATH entries the way the host filesystem does: case-insensitively on Windows
+fn same_path_entry(a: &str, b: &str) -> bool {
+    let a = a.trim_end_matches(['\\', '/']);
+    let b = b.trim_end_matches(['\\', '/']);
+    if cfg!(windows) {
+        a.to_lowercase() == b.to_lowercase()
+    } else {
+        a == b
+    }
+}
+
+// Checks whether a PATH-style variable already lists the given directory
+fn path_contains_entry(path_var: &str, entry: &str) -> bool {
+    std::env::split_paths(path_var).any(|existing| same_path_entry(&existing.to_string_lossy(), entry))
+}
+
+// Fills the report from the installed JDK's release file, which beats what the API claimed.
+// architecture is the Adoptium architecture that was requested
+fn apply_release(report: &mut InstallReport, release: Option<JdkRelease>, architecture: &str) {
+    if let Some(release) = &release {
+        if release.implementor.is_some() {
+            report.vendor = release.implementor.clone();
+        }
+        if !release.matches_arch(architecture) {
+            warn!(
+                "The installed JDK is built for {}, {} was requested.",
+                release.os_arch.as_deref().unwrap_or_default(),
+                architecture
+            );
+        }
+    }
+    report.release = release;
+}
+
+const HELLO_WORLD_MARKER: &str = "java-setup toolchain ok";
+
+// Compiles and runs a HelloWorld with the JDK's javac and java. Returns false without
+// running anything when there is no javac (a JRE)
+pub fn verify_toolchain(java_home: &Path) -> Result<bool, JavaSetupError> {
+    let jdk = InstalledJdk::new(java_home);
+    let javac_path = jdk.javac_exe();
+    if !javac_path.exists() {
+        info!("No javac in {}, skipping the toolchain check.", java_home.display());
+        return Ok(false);
+    }
+
+    let nanos = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|elapsed| elapsed.subsec_nanos())
+        .unwrap_or(0);
+    let work_dir = std::env::temp_dir().join(format!("java-setup-toolchain-{}-{}", std::process::id(), nanos));
+    fs::create_dir_all(&work_dir)?;
+    let result = run_hello_world(&javac_path, &jdk.java_exe(), &work_dir);
+    let _ = fs::remove_dir_all(&work_dir);
+    Ok(result.map(|()| true)?)
+}
+
+fn run_hello_world(javac_path: &Path, java_path: &Path, work_dir: &Path) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let failed = |step: &str, output: &std::process::Output| {
+        Box::new(JavaSetupError::VerificationFailed(format!(
+            "{} exited with {}: {}{}",
+            step,
+            output.status,
+            String::from_utf8_lossy(&output.stdout),
+            String::from_utf8_lossy(&output.stderr).trim()
+        ))) as Box<dyn std::error::Error + Send + Sync>
+    };
+
+    let source = format!(
+        "public class HelloWorld {{ public static void main(String[] args) {{ System.out.println(\"{}\"); }} }}\n",
+        HELLO_WORLD_MARKER
+    );
+    fs::write(work_dir.join("HelloWorld.java"), source)?;
+
+    let output = std::process::Command::new(javac_path)
+        .arg("HelloWorld.java")
+        .current_dir(work_dir)
+        .output()?;
+    if !output.status.success() {
+        return Err(failed("javac", &output));
+    }
+
+    let output = std::process::Command::new(java_path)
+        .args(["-cp", ".", "HelloWorld"])
+        .current_dir(work_dir)
+        .output()?;
+    if !output.status.success() {
+        return Err(failed("java", &output));
+    }
+    if !String::from_utf8_lossy(&output.stdout).contains(HELLO_WORLD_MARKER) {
+        return Err(Box::new(JavaSetupError::VerificationFailed(format!(
+            "HelloWorld printed {:?}",
+            String::from_utf8_lossy(&output.stdout).trim()
+        ))));
+    }
+    Ok(())
+}
+
+// Runs <java_home>/bin/java -version and returns the reported version string
+pub fn verify_installation(java_home: &Path) -> Result<String, JavaSetupError> {
+    let java_path = InstalledJdk::new(java_home).java_exe();
+    if !java_path.exists() {
+        return Err(JavaSetupError::VerificationFailed(format!(
+            "{} does not exist",
+            java_path.display()
+        )));
+    }
+
+    // A missing exec bit or a binary for another architecture fails to start at all
+    let output = std::process::Command::new(&java_path).arg("-version").output().map_err(|e| {
+        JavaSetupError::VerificationFailed(format!("could not run {}: {}", java_path.display(), e))
+    })?;
+    if !output.status.success() {
+        return Err(JavaSetupError::VerificationFailed(format!(
+            "{} -version exited with {}",
+            java_path.display(),
+            output.status
+        )));
+    }
+
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    parse_version_output(&stderr)
+        .ok_or_else(|| {
+            JavaSetupError::VerificationFailed(format!(
+                "unexpected java -version output: {}",
+                stderr.trim()
+            ))
+        })
+}
+
+// java prints its version to stderr, e.g. openjdk version "17.0.9" 2023-10-17, possibly after
+// lines like "Picked up JAVA_TOOL_OPTIONS: ..." when such a variable is set
+fn parse_version_output(stderr: &str) -> Option<String> {
+    stderr
+        .lines()
+        .find(|line| line.contains(" version \""))
+        .and_then(|line| line.split('"').nth(1))
+        .map(|version| version.to_string())
+}
+
+impl EnvironmentVariableConfigurator {
+    // Returns the persistent scope that already contained the JDK bin, if any
+    pub unsafe fn configure(&self) -> Result<Option<EnvScope>, Box<dyn std::error::Error + Send + Sync>> {
+        let jdk_bin_path = InstalledJdk::new(&self.install_path).bin_dir().to_string_lossy().into_owned();
+        let current_path = std::env::var("PATH").unwrap_or_default();
+        debug!("Actual PATH: {}", current_path);
+
+        // Update the current process PATH
+        if !self.update_current_process {
+            info!("Leaving the PATH of this process unchanged.");
+        } else if !path_contains_entry(&current_path, &jdk_bin_path) {
+            let new_path = format!("{}{}{}", current_path, PATH_SEPARATOR, jdk_bin_path);
+            unsafe {
+            std::env::set_var("PATH", &new_path);
+            }
+            info!("Updated PATH with JDK bin.");
+        } else {
+            info!("The PATH already contains the JDK bin.");
+        }
+
+        let configured_scope = self.add_to_persistent_path(&jdk_bin_path)?;
+        if self.configure_java_home {
+            unsafe {
+                self.set_java_home()?;
+            }
+        }
+        Ok(configured_scope)
+    }
+
+    fn add_to_persistent_path(&self, jdk_bin_path: &str) -> Result<Option<EnvScope>, Box<dyn std::error::Error + Send + Sync>> {
+        if self.backend == EnvBackend::Profile {
+            return self.add_to_profiles(jdk_bin_path, false);
+        }
+
+        // A fresh session reads PATH from the registry, so check both persistent scopes
+        for scope in [EnvScope::User, EnvScope::Machine] {
+            match self.read_persistent_path(scope) {
+                Ok(persistent_path) if path_contains_entry(&persistent_path, jdk_bin_path) => {
+                    info!("The {:?} PATH already contains the JDK, nothing to update.", scope);
+                    return Ok(Some(scope));
+                }
+                Ok(_) => {}
+                Err(e) => warn!("Could not read the {:?} PATH: {}", scope, e),
+            }
+        }
+
+        match self.backend {
+            EnvBackend::Registry => self.add_to_user_path_with_registry(jdk_bin_path)?,
+            EnvBackend::PowerShell => self.add_to_user_path_with_powershell(jdk_bin_path)?,
+            EnvBackend::Profile => unreachable!("handled above"),
+        }
+
+        Ok(None)
+    }
+
+    // Points JAVA_HOME at install_path for this process and new sessions, overwriting any previous value
+    unsafe fn set_java_home(&self) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        if self.update_current_process {
+            unsafe {
+                std::env::set_var("JAVA_HOME", &self.install_path);
+            }
+        }
+        match self.backend {
+            EnvBackend::Registry => {
+                if windows_env::read_var(EnvScope::User, "JAVA_HOME")?.as_deref() != Some(self.install_path.as_str()) {
+                    windows_env::write_user_var("JAVA_HOME", &self.install_path)?;
+                    windows_env::broadcast_change();
+                }
+            }
+            EnvBackend::PowerShell => {
+                let script_content = format!(
+                    r#"[Environment]::SetEnvironmentVariable("JAVA_HOME", '{}', "User")"#,
+                    self.install_path.replace('\'', "''")
+                );
+                run_powershell_script("set_java_home.ps1", &script_content)?;
+            }
+            EnvBackend::Profile => {
+                for profile in shell_profile::profile_paths() {
+                    shell_profile::set_var(&profile, "JAVA_HOME", &self.install_path)?;
+                }
+            }
+        }
+        info!("JAVA_HOME set to {}", self.install_path);
+        Ok(())
+    }
+
+    // Adds the JDK bin to the shell profiles, Some(User) when every profile already had it
+    fn add_to_profiles(&self, jdk_bin_path: &str, move_to_end: bool) -> Result<Option<EnvScope>, Box<dyn std::error::Error + Send + Sync>> {
+        let profiles = shell_profile::profile_paths();
+        if profiles.is_empty() {
+            return Err(Box::new(JavaSetupError::EnvUpdateFailed("HOME is not set, no shell profile to update".to_string())));
+        }
+        let mut changed = false;
+        for profile in &profiles {
+            if shell_profile::add_entry(profile, jdk_bin_path, move_to_end)? {
+                info!("Updated {}.", profile.display());
+                changed = true;
+            }
+        }
+        if changed {
+            return Ok(None);
+        }
+        info!("The shell profiles already contain the JDK, nothing to update.");
+        Ok(Some(EnvScope::User))
+    }
+
+    // Writes HKCU\Environment\Path directly and broadcasts WM_SETTINGCHANGE
+    fn add_to_user_path_with_registry(&self, jdk_bin_path: &str) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let user_path = windows_env::read_var(EnvScope::User, "Path")?.unwrap_or_default();
+        if path_contains_entry(&user_path, jdk_bin_path) {
+            info!("PATH already contains the JDK.");
+            return Ok(());
+        }
+
+        let new_path = if user_path.is_empty() {
+            jdk_bin_path.to_string()
+        } else {
+            format!("{};{}", user_path.trim_end_matches(';'), jdk_bin_path)
+        };
+        windows_env::write_user_var("Path", &new_path)?;
+        windows_env::broadcast_change();
+        info!("Updated user's PATH.");
+        Ok(())
+    }
+
+    fn add_to_user_path_with_powershell(&self, jdk_bin_path: &str) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        // Generates and runs the PowerShell script to update the user's PATH
+        let script_content = format!(
+            r#"
+$jdkPath = '{}'
+$userPath = [Environment]::GetEnvironmentVariable("PATH", "User")
+# -ieq matches entries case-insensitively, like the Windows filesystem
+$existing = $userPath -split ';' | Where-Object {{ $_.TrimEnd('\') -ieq $jdkPath.TrimEnd('\') }}
+if (-not $existing) {{
+    $newPath = "$userPath;$jdkPath"
+    [Environment]::SetEnvironmentVariable("PATH", $newPath, "User")
+    Write-Host "Updated user's PATH."
+}} else {{
+    Write-Host "PATH already contains the JDK."
+}}
+"#,
+            jdk_bin_path.replace('\'', "''")
+        );
+
+        let output = run_powershell_script("add_jdk_to_path.ps1", &script_content)?;
+        debug!("{}", String::from_utf8_lossy(&output.stdout).trim_end());
+        debug!("Powershell script executed correctly.");
+        Ok(())
+    }
+
+    // Strips the JDK bin from the user's PATH and clears JAVA_HOME where it points at install_path.
+    // What is already gone is skipped, so running it again changes nothing
+    pub unsafe fn unconfigure(&self) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let jdk_bin_path = InstalledJdk::new(&self.install_path).bin_dir().to_string_lossy().into_owned();
+        if self.update_current_process {
+            let current_path = std::env::var_os("PATH").unwrap_or_default();
+            let kept = std::env::split_paths(&current_path)
+                .filter(|entry| !same_path_entry(&entry.to_string_lossy(), &jdk_bin_path));
+            if let Ok(new_path) = std::env::join_paths(kept)
+                && new_path != current_path
+            {
+                unsafe {
+                    std::env::set_var("PATH", new_path);
+                }
+            }
+            if std::env::var("JAVA_HOME").is_ok_and(|java_home| same_path_entry(&java_home, &self.install_path)) {
+                unsafe {
+                    std::env::remove_var("JAVA_HOME");
+                }
+            }
+        }
+
+        match self.backend {
+            EnvBackend::Registry => {
+                let mut changed = false;
+                let user_path = windows_env::read_var(EnvScope::User, "Path")?.unwrap_or_default();
+                if path_contains_entry(&user_path, &jdk_bin_path) {
+                    // Splits on ';' only, so the other entries are written back exactly as they were
+                    let kept: Vec<&str> = user_path
+                        .split(';')
+                        .filter(|entry| !entry.is_empty() && !same_path_entry(entry, &jdk_bin_path))
+                        .collect();
+                    windows_env::write_user_var("Path", &kept.join(";"))?;
+                    changed = true;
+                }
+                if windows_env::read_var(EnvScope::User, "JAVA_HOME")?
+                    .is_some_and(|java_home| same_path_entry(&java_home, &self.install_path))
+                {
+                    windows_env::delete_user_var("JAVA_HOME")?;
+                    changed = true;
+                }
+                if changed {
+                    windows_env::broadcast_change();
+                    info!("Removed the JDK from the user's environment.");
+                }
+            }
+            EnvBackend::PowerShell => {
+                let script_content = format!(
+                    r#"
+$jdkPath = '{}'
+$installPath = '{}'
+$userPath = [Environment]::GetEnvironmentVariable("PATH", "User")
+if ($userPath) {{
+    # Splices out only the JDK bin, the other entries keep their order and spelling
+    $entries = @($userPath -split ';' | Where-Object {{ $_ }})
+    $kept = @($entries | Where-Object {{ $_.TrimEnd('\') -ine $jdkPath.TrimEnd('\') }})
+    if ($kept.Count -ne $entries.Count) {{
+        [Environment]::SetEnvironmentVariable("PATH", ($kept -join ';'), "User")
+        Write-Host "Removed the JDK from the user's PATH."
+    }}
+}}
+$javaHome = [Environment]::GetEnvironmentVariable("JAVA_HOME", "User")
+if ($javaHome -and $javaHome.TrimEnd('\') -ieq $installPath.TrimEnd('\')) {{
+    [Environment]::SetEnvironmentVariable("JAVA_HOME", $null, "User")
+    Write-Host "Removed JAVA_HOME."
+}}
+"#,
+                    jdk_bin_path.replace('\'', "''"),
+                    self.install_path.replace('\'', "''")
+                );
+                let output = run_powershell_script("remove_jdk_from_path.ps1", &script_content)?;
+                debug!("{}", String::from_utf8_lossy(&output.stdout).trim_end());
+            }
+            EnvBackend::Profile => {
+                for profile in shell_profile::profile_paths() {
+                    let removed_path = shell_profile::remove_entry(&profile, &jdk_bin_path)?;
+                    let removed_java_home = shell_profile::remove_var(&profile, "JAVA_HOME", &self.install_path)?;
+                    if removed_path || removed_java_home {
+                        info!("Updated {}.", profile.display());
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    // Writes a script that forwards its arguments to the installed java
+    pub fn write_launcher_shim(&self, shim_dir: &Path) -> Result<PathBuf, Box<dyn std::error::Error + Send + Sync>> {
+        fs::create_dir_all(shim_dir)?;
+        let jdk = InstalledJdk::new(&self.install_path);
+
+        let shim_path = if cfg!(windows) {
+            let shim_path = shim_dir.join("java.bat");
+            let content = format!("@echo off\r\n\"{}\" %*\r\n", jdk.java_exe().display());
+            manifest::write_atomically(&shim_path, &content)?;
+            shim_path
+        } else {
+            let shim_path = shim_dir.join("java");
+            // Single-quoted shell strings only need their quotes escaped
+            let java = jdk.java_exe().to_string_lossy().replace('\'', r"'\''");
+            manifest::write_atomically(&shim_path, &format!("#!/bin/sh\nexec '{}' \"$@\"\n", java))?;
+            #[cfg(unix)]
+            {
+                use std::os::unix::fs::PermissionsExt;
+                fs::set_permissions(&shim_path, fs::Permissions::from_mode(0o755))?;
+            }
+            shim_path
+        };
+
+        info!("Wrote the java launcher to {}", shim_path.display());
+        Ok(shim_path)
+    }
+
+    // Reads the PATH stored in the given scope, as a new session would see it
+    fn read_persistent_path(&self, scope: EnvScope) -> Result<String, Box<dyn std::error::Error + Send + Sync>> {
+        match self.backend {
+            EnvBackend::Registry => Ok(windows_env::read_var(scope, "Path")?.unwrap_or_default()),
+            EnvBackend::PowerShell => {
+                let scope = match scope {
+                    EnvScope::User => "User",
+                    EnvScope::Machine => "Machine",
+                };
+                let output = run_powershell_script(
+                    "read_path.ps1",
+                    &format!(r#"Write-Output ([Environment]::GetEnvironmentVariable("PATH", "{}"))"#, scope),
+                )?;
+                Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+            }
+            // The profiles only hold the entries written here, the system PATH stays as it is
+            EnvBackend::Profile => match scope {
+                EnvScope::User => {
+                    let mut dirs = Vec::new();
+                    for profile in shell_profile::profile_paths() {
+                        dirs.extend(shell_profile::exported_dirs(&profile)?);
+                    }
+                    Ok(dirs.join(PATH_SEPARATOR))
+                }
+                EnvScope::Machine => Ok(String::new()),
+            },
+        }
+    }
+
+    fn write_user_path(&self, value: &str) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        match self.backend {
+            EnvBackend::Registry => {
+                windows_env::write_user_var("Path", value)?;
+                windows_env::broadcast_change();
+            }
+            EnvBackend::PowerShell => {
+                // Single-quoted PowerShell strings only need their quotes doubled
+                let script_content = format!(
+                    r#"[Environment]::SetEnvironmentVariable("PATH", '{}', "User")"#,
+                    value.replace('\'', "''")
+                );
+                run_powershell_script("write_path.ps1", &script_content)?;
+            }
+            EnvBackend::Profile => unreachable!("profiles are edited through add_to_profiles"),
+        }
+        Ok(())
+    }
+
+    // Makes sure a fresh session resolves the installed java, moving its bin to the front of PATH if needed
+    pub unsafe fn pin_path_order(&self) -> Result<PathOrderVerification, Box<dyn std::error::Error + Send + Sync>> {
+        let jdk_bin_path = InstalledJdk::new(&self.install_path).bin_dir().to_string_lossy().into_owned();
+        let resolved_java = self.resolve_fresh_java_home()?;
+        if self.is_installed_java(&resolved_java) {
+            return Ok(PathOrderVerification { resolved_java, adjusted: false, verified: true });
+        }
+        info!("A fresh session resolves another java, moving the JDK bin to the front of PATH.");
+
+        // Update the current process PATH
+        if self.update_current_process {
+            let current_path = std::env::var("PATH").unwrap_or_default();
+            let mut entries: Vec<String> = std::env::split_paths(&current_path)
+                .map(|entry| entry.to_string_lossy().into_owned())
+                .filter(|entry| !same_path_entry(entry, &jdk_bin_path))
+                .collect();
+            entries.insert(0, jdk_bin_path.clone());
+            unsafe {
+                std::env::set_var("PATH", entries.join(PATH_SEPARATOR));
+            }
+        }
+
+        if self.backend == EnvBackend::Profile {
+            // Every line prepends to PATH, so the last one of the profile comes first
+            self.add_to_profiles(&jdk_bin_path, true)?;
+            info!("Moved the JDK to the end of the shell profiles.");
+        } else {
+            // Moves the JDK bin to the front of the user's PATH
+            let user_path = self.read_persistent_path(EnvScope::User)?;
+            let others = std::env::split_paths(&user_path)
+                .map(|entry| entry.to_string_lossy().into_owned())
+                .filter(|entry| !entry.is_empty() && !same_path_entry(entry, &jdk_bin_path));
+            let new_path: Vec<String> = std::iter::once(jdk_bin_path.clone()).chain(others).collect();
+            self.write_user_path(&new_path.join(";"))?;
+            info!("Moved the JDK to the front of the user's PATH.");
+        }
+
+        // Machine PATH entries still win over the user's, so the check can fail again
+        let resolved_java = self.resolve_fresh_java_home()?;
+        let verified = self.is_installed_java(&resolved_java);
+        Ok(PathOrderVerification { resolved_java, adjusted: true, verified })
+    }
+
+    fn is_installed_java(&self, java_home: &Option<PathBuf>) -> bool {
+        java_home
+            .as_ref()
+            .map(|java_home| same_path_entry(&java_home.to_string_lossy(), &self.install_path))
+            .unwrap_or(false)
+    }
+
+    // Runs java from the persistent PATH a new session would get and returns its java.home
+    fn resolve_fresh_java_home(&self) -> Result<Option<PathBuf>, Box<dyn std::error::Error + Send + Sync>> {
+        let mut command = if self.backend == EnvBackend::Profile {
+            // A login shell reads ~/.profile the way a new terminal does
+            let mut command = std::process::Command::new("sh");
+            command.args(["-lc", "exec java -XshowSettings:properties -version"]);
+            command
+        } else {
+            // Machine entries come before the user's ones in a new session
+            let fresh_path = format!(
+                "{};{}",
+                self.read_persistent_path(EnvScope::Machine)?,
+                self.read_persistent_path(EnvScope::User)?
+            );
+            let mut command = std::process::Command::new("java");
+            command.env("PATH", &fresh_path).args(["-XshowSettings:properties", "-version"]);
+            command
+        };
+
+        let output = match command.output() {
+            Ok(output) => output,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(Box::new(e)),
+        };
+
+        // The settings are printed to stderr as "    java.home = C:\..."
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        Ok(stderr
+            .lines()
+            .filter_map(|line| line.trim().strip_prefix("java.home"))
+            .filter_map(|rest| rest.trim_start().strip_prefix('='))
+            .map(|java_home| PathBuf::from(java_home.trim()))
+            .next())
+    }
+}
+
+// Writes a PowerShell script to %TEMP% and runs it, capturing its output. Fails with EnvUpdateFailed
+// when the script exits with an error
+// The error for a target that can't be written without administrator rights
+fn needs_elevation(target: &Path, error: &(dyn std::error::Error + 'static)) -> Box<dyn std::error::Error + Send + Sync> {
+    Box::new(JavaSetupError::NeedsElevation {
+        operation: format!("installing to {}", target.display()),
+        reason: error.to_string(),
+    })
+}
+
+// Tells the event channel how a setup ended, install_many also sends it once the setup is gone
+async fn send_outcome(events: Option<&ChannelReporter>, result: &Result<InstallReport, JavaSetupError>) {
+    if let Some(events) = events {
+        let event = match result {
+            Ok(report) => InstallEvent::Completed { install_path: report.install_path.clone() },
+            Err(e) => InstallEvent::Failed(e.to_string()),
+        };
+        events.finish(event).await;
+    }
+}
+
+fn run_powershell_script(script_name: &str, script_content: &str) -> Result<std::process::Output, Box<dyn std::error::Error + Send + Sync>> {
+    // %TEMP% on Windows, so the script never lands in the working directory
+    let script_path = std::env::temp_dir().join(script_name);
+    debug!("Creating PowerShell script at: {}", script_path.display());
+    fs::write(&script_path, script_content)?;
+
+    let output = std::process::Command::new("powershell")
+        .args(["-ExecutionPolicy", "Bypass", "-File"])
+        .arg(&script_path)
+        .output()?;
+    if !output.status.success() {
+        return Err(Box::new(JavaSetupError::EnvUpdateFailed(format!(
+            "{} exited with {}: {}",
+            script_name,
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim()
+        ))));
+    }
+    Ok(output)
+}
+
+impl JavaSetup {
+    // Named settings with temp directory defaults for the download and extract paths, see JavaSetupBuilder
+    pub fn builder() -> JavaSetupBuilder {
+        JavaSetupBuilder::new()
+    }
+
+    pub fn new(java_version: &str, download_path: &str, extract_path: &str, install_path: &str) -> Self {
+        let java_url = Vendor::Adoptium.api().metadata_url(&PackageQuery {
+            java_version,
+            image_type: ImageType::Jdk.as_str(),
+            os: adoptium_os(),
+            architecture: adoptium_arch(),
+        });
+        let cleanup = CleanupGuard::default();
+        let progress: Arc<dyn ProgressReporter> = Arc::new(NoopReporter);
+        JavaSetup {
+            downloader: Downloader {
+                java_version: java_version.to_string(),
+                download_path: download_path.to_string(),
+                java_url,
+                max_bytes_per_sec: None,
+                checksum_policy: ChecksumPolicy::default(),
+                cleanup: cleanup.clone(),
+                asset_name: None,
+                client: http_client(&[], None).unwrap_or_default(),
+                pinned_certs: Vec::new(),
+                proxy: None,
+                max_metadata_bytes: DEFAULT_MAX_METADATA_BYTES,
+                vendor: None,
+                jdk_vendor: Vendor::default(),
+                architecture: adoptium_arch().to_string(),
+                image_type: ImageType::default(),
+                metadata: None,
+                checksum_redownloads: DEFAULT_CHECKSUM_REDOWNLOADS,
+                source: None,
+                retry_policy: None,
+                retry_attempts: DEFAULT_RETRY_ATTEMPTS,
+                retry_base_delay: DEFAULT_RETRY_BASE_DELAY,
+                progress: progress.clone(),
+                cancel: CancellationToken::new(),
+            },
+            extractor: Extractor {
+                download_path: download_path.to_string(),
+                extract_path: extract_path.to_string(),
+                registry: ExtractorRegistry::default(),
+                duplicate_policy: DuplicateEntryPolicy::default(),
+                progress: progress.clone(),
+                cancel: CancellationToken::new(),
+            },
+            installer: Installer {
+                extract_path: extract_path.to_string(),
+                install_path: install_path.to_string(),
+                copy_filter: None,
+                existing_policy: ExistingInstallPolicy::default(),
+                mode: InstallMode::default(),
+                jdk_root_subpath: None,
+                expected_entries: None,
+                image_type: ImageType::Jdk,
+                progress: progress.clone(),
+            },
+            env_configurator: EnvironmentVariableConfigurator {
+                install_path: install_path.to_string(),
+                pin_path_order: false,
+                backend: EnvBackend::default(),
+                shim_dir: None,
+                update_current_process: true,
+                configure_java_home: true,
+            },
+            keep_extracted: false,
+            write_hash_index: false,
+            hash_threads: None,
+            cleanup,
+            supplementary_packages: Vec::new(),
+            supplementary_path: String::new(),
+            confirm_download: None,
+            validate_java_home: false,
+            verify_launch: true,
+            verify_toolchain: false,
+            elevate_on_access_denied: false,
+            app_id: None,
+            streaming_install: false,
+            yield_during_extract: false,
+            verify_before_swap: false,
+            expected_lockfile: None,
+            events: None,
+            force: false,
+            local_archive: None,
+            keep_temp_files: false,
+            downloaded_archive: None,
+            new_download_dir: None,
+            new_extract_dir: None,
+            existing_extract_entries: None,
+        }
+    }
+
+    // Installs an archive already on disk (a CI cache, a vendored file, ...) without the Adoptium API
+    // or any download, the extract, install and configure steps are the same as for setup. Fails
+    // with UnsupportedArchive when no built-in extractor handles the file's extension.
+    pub fn from_archive<P: AsRef<Path>>(archive_path: P, extract_path: &str, install_path: &str) -> Result<Self, JavaSetupError> {
+        let archive_path = fs::canonicalize(archive_path.as_ref()).map_err(|e| {
+            std::io::Error::new(e.kind(), format!("archive {}: {}", archive_path.as_ref().display(), e))
+        })?;
+        if !archive_path.is_file() {
+            return Err(JavaSetupError::UnsupportedArchive(archive_path.display().to_string()));
+        }
+        // Holds what a download directory would (the elevated install staging), and is deleted
+        // the same way, so it must not be the archive's own directory
+        let work_dir = format!("{}.download/", extract_path.trim_end_matches(['/', '\\']));
+        let mut setup = JavaSetup::new("", &work_dir, extract_path, install_path);
+        let file_name = archive_path.file_name().unwrap_or_default().to_string_lossy().into_owned();
+        if setup.extractor.registry.find(&file_name).is_none() {
+            return Err(JavaSetupError::UnsupportedArchive(file_name));
+        }
+        setup.extractor.download_path = archive_path.to_string_lossy().into_owned();
+        setup.local_archive = Some(archive_path);
+        Ok(setup)
+    }
+
+    // Moves relative download and extract paths under temp_dir()/<app_id> so several apps don't collide
+    pub fn set_app_id(&mut self, app_id: &str) -> Result<(), JavaSetupError> {
+        let mut components = Path::new(app_id).components();
+        if !matches!((components.next(), components.next()), (Some(std::path::Component::Normal(_)), None)) {
+            return Err(JavaSetupError::InvalidAppId(app_id.to_string()));
+        }
+
+        let old_root = self.namespace_dir();
+        let new_root = std::env::temp_dir().join(app_id);
+        let rebase = |path: &mut String| {
+            // Paths namespaced by a previous app id go back to their relative form first
+            let relative = match &old_root {
+                Some(old_root) => Path::new(path.as_str()).strip_prefix(old_root).map(Path::to_path_buf).ok(),
+                None => None,
+            };
+            let relative = relative.unwrap_or_else(|| PathBuf::from(path.as_str()));
+            if relative.is_relative() {
+                let mut rebased = new_root.join(&relative).to_string_lossy().into_owned();
+                // Keeps the trailing separator that marks download_path as a directory
+                if (path.ends_with('/') || path.ends_with('\\')) && !rebased.ends_with(std::path::MAIN_SEPARATOR) {
+                    rebased.push(std::path::MAIN_SEPARATOR);
+                }
+                *path = rebased;
+            }
+        };
+        rebase(&mut self.downloader.download_path);
+        rebase(&mut self.extractor.download_path);
+        rebase(&mut self.extractor.extract_path);
+        rebase(&mut self.installer.extract_path);
+
+        self.app_id = Some(app_id.to_string());
+        Ok(())
+    }
+
+    // Working directory root of this app, None without an app id
+    pub fn namespace_dir(&self) -> Option<PathBuf> {
+        self.app_id.as_ref().map(|app_id| std::env::temp_dir().join(app_id))
+    }
+
+    // Removes leftover downloads and extracted files. With an app id the whole namespace
+    // is removed, otherwise only this setup's download directory and extract path
+    pub fn clean_temp(&self) -> Result<(), JavaSetupError> {
+        let dirs = match self.namespace_dir() {
+            Some(namespace_dir) => vec![namespace_dir],
+            None => vec![self.download_dir(), PathBuf::from(&self.extractor.extract_path)],
+        };
+        for dir in dirs {
+            if dir.exists() {
+                fs::remove_dir_all(&dir)?;
+                info!("Deleted {}", dir.display());
+            }
+        }
+        Ok(())
+    }
+
+    // By default the extracted archive is removed once the install succeeds
+    pub fn set_keep_extracted(&mut self, keep_extracted: bool) {
+        self.keep_extracted = keep_extracted;
+    }
+
+    // Lets another task (e.g. a Cancel button) stop setup and install_and_activate.
+    // Requests, downloads and extraction stop as soon as token is cancelled, the partial files are
+    // removed and the setup fails with Cancelled. Once the install of the JDK has started it is
+    // finished, a later cancel has no effect
+    pub fn set_cancel_token(&mut self, token: CancellationToken) {
+        self.downloader.cancel = token.clone();
+        self.extractor.cancel = token;
+    }
+
+    // Keeps the downloaded archive and the extracted files after a successful install, for debugging
+    pub fn set_keep_temp_files(&mut self, keep_temp_files: bool) {
+        self.keep_temp_files = keep_temp_files;
+    }
+
+    // Lets the caller inspect the resolved package (name, size, checksum) and decline the download
+    pub fn set_confirm_download<F>(&mut self, confirm: F)
+    where
+        F: FnMut(&ReleaseAsset) -> bool + Send + Sync + 'static,
+    {
+        self.confirm_download = Some(Box::new(confirm));
+    }
+
+    // Caps the download bandwidth, None or 0 downloads at full speed
+    pub fn set_max_bytes_per_sec(&mut self, max_bytes_per_sec: Option<u64>) {
+        self.downloader.max_bytes_per_sec = max_bytes_per_sec.filter(|limit| *limit > 0);
+    }
+
+    // Ignores releases of other vendors in the API response, e.g. Some("eclipse")
+    pub fn set_vendor(&mut self, vendor: Option<&str>) {
+        self.downloader.vendor = vendor.map(|vendor| vendor.to_string());
+    }
+
+    // Requests builds for this Adoptium architecture (x64, aarch64, arm, ...) instead of the host's,
+    // e.g. x64 on Apple Silicon to run under Rosetta
+    pub fn set_architecture(&mut self, architecture: &str) {
+        self.downloader.architecture = architecture.to_string();
+        self.downloader.refresh_java_url();
+    }
+
+    // Installs a JRE instead of the full JDK. verify_toolchain needs javac and so fails on a JRE
+    pub fn set_image_type(&mut self, image_type: ImageType) {
+        self.downloader.image_type = image_type;
+        self.downloader.refresh_java_url();
+    }
+
+    // Resolves the JDK from another vendor's API (Corretto, Zulu, GraalVM) instead of Adoptium.
+    // Supplementary packages still come from Adoptium
+    pub fn set_jdk_vendor(&mut self, vendor: Vendor) {
+        self.downloader.jdk_vendor = vendor;
+        self.downloader.refresh_java_url();
+    }
+
+    // Limits the size of Adoptium API responses, 4 MiB by default
+    pub fn set_max_metadata_bytes(&mut self, max_metadata_bytes: u64) {
+        self.downloader.max_metadata_bytes = max_metadata_bytes;
+    }
+
+    // Receives the progress of every stage instead of the default NoopReporter
+    pub fn set_progress_reporter<R: ProgressReporter + 'static>(&mut self, reporter: R) {
+        let reporter: Arc<dyn ProgressReporter> = Arc::new(reporter);
+        match &self.events {
+            // The channel keeps getting the events alongside the new reporter
+            Some(events) => self.set_event_reporter(ChannelReporter::new(events.sender.clone(), reporter)),
+            None => self.use_progress_reporter(reporter),
+        }
+    }
+
+    // Calls callback with the bytes downloaded so far and the Content-Length (None when the server
+    // doesn't send one) after every chunk of the JDK download. Shorthand for a ProgressReporter that
+    // only implements bytes_progress, and replaces the current reporter like set_progress_reporter
+    pub fn set_progress_callback<F: FnMut(u64, Option<u64>) + Send + 'static>(&mut self, callback: F) {
+        self.set_progress_reporter(progress::BytesCallback(std::sync::Mutex::new(callback)));
+    }
+
+    // Sends every progress call as an InstallEvent, then Completed or Failed when setup or
+    // install_and_activate returns. A reporter set before or after keeps receiving the calls.
+    // Use a bounded channel: a lagging receiver only loses byte, entry and file events, see ChannelReporter
+    pub fn set_event_sender(&mut self, sender: tokio::sync::mpsc::Sender<InstallEvent>) {
+        let inner = match &self.events {
+            Some(events) => events.inner.clone(),
+            None => self.downloader.progress.clone(),
+        };
+        self.set_event_reporter(ChannelReporter::new(sender, inner));
+    }
+
+    fn set_event_reporter(&mut self, reporter: ChannelReporter) {
+        let reporter = Arc::new(reporter);
+        self.events = Some(reporter.clone());
+        self.use_progress_reporter(reporter);
+    }
+
+    fn use_progress_reporter(&mut self, reporter: Arc<dyn ProgressReporter>) {
+        self.downloader.progress = reporter.clone();
+        self.extractor.progress = reporter.clone();
+        self.installer.progress = reporter;
+    }
+
+    // Pre-flight check that the Adoptium API answers, see Downloader::check_connectivity
+    pub async fn check_connectivity(&self) -> Result<(), JavaSetupError> {
+        self.downloader.check_connectivity().await
+    }
+
+    // Feature releases available to install, see Downloader::available_versions
+    pub async fn available_versions(&self) -> Result<AvailableVersions, JavaSetupError> {
+        self.downloader.available_versions().await
+    }
+
+    // Gets the JDK archive from source instead of Adoptium, e.g. a torrent or an internal mirror
+    pub fn set_source<S: JdkSource + 'static>(&mut self, source: S) {
+        self.downloader.source = Some(Arc::new(source));
+    }
+
+    // Resolves packages from a saved API response (a JSON array of releases) instead of calling
+    // the API, so only the binary itself is downloaded
+    pub fn set_metadata(&mut self, metadata: serde_json::Value) {
+        self.downloader.metadata = Some(metadata);
+    }
+
+    // Same as set_metadata with a response saved to disk, e.g. by a CI step
+    pub fn set_metadata_file<P: AsRef<Path>>(&mut self, path: P) -> Result<(), JavaSetupError> {
+        let content = fs::read_to_string(path.as_ref())?;
+        self.set_metadata(serde_json::from_str(&content)?);
+        Ok(())
+    }
+
+    // Pins the package by its Adoptium name, e.g. OpenJDK17U-jdk_x64_windows_hotspot_17.0.9_9.zip
+    pub fn set_asset_name(&mut self, asset_name: Option<&str>) {
+        self.downloader.asset_name = asset_name.map(|asset_name| asset_name.to_string());
+    }
+
+    // Rejects download and metadata hosts whose certificate doesn't match one of the pinned fingerprints
+    pub fn pin_cert(&mut self, fingerprint: &str) -> Result<(), JavaSetupError> {
+        self.downloader.pin_cert(fingerprint)
+    }
+
+    // See Downloader::set_proxy
+    pub fn set_proxy(&mut self, proxy_url: Option<&str>) -> Result<(), JavaSetupError> {
+        self.downloader.set_proxy(proxy_url)
+    }
+
+    // Replaces the built-in exponential backoff with custom retry logic
+    pub fn set_retry_policy<F>(&mut self, policy: F)
+    where
+        F: Fn(&(dyn std::error::Error + Send + Sync + 'static), u32) -> RetryDecision + Send + Sync + 'static,
+    {
+        self.downloader.retry_policy = Some(Arc::new(policy));
+    }
+
+    // Attempts of a request, download or stage before the built-in backoff gives up, 3 by default
+    pub fn set_retry_attempts(&mut self, retry_attempts: u32) {
+        self.downloader.retry_attempts = retry_attempts;
+    }
+
+    // Wait before the first retry of the built-in backoff, doubled for each further one, 500 ms by default
+    pub fn set_retry_base_delay(&mut self, retry_base_delay: Duration) {
+        self.downloader.retry_base_delay = retry_base_delay;
+    }
+
+    pub fn set_checksum_policy(&mut self, checksum_policy: ChecksumPolicy) {
+        self.downloader.checksum_policy = checksum_policy;
+    }
+
+    // How many times a download failing its checksum is repeated (1 by default, 0 fails right away)
+    pub fn set_checksum_redownloads(&mut self, redownloads: u32) {
+        self.downloader.checksum_redownloads = redownloads;
+    }
+
+    // Adds or replaces the handler used for archives ending with extension (e.g. "tar.xz")
+    pub fn register_archive_extractor<E>(&mut self, extension: &str, extractor: E)
+    where
+        E: ArchiveExtractor + 'static,
+    {
+        self.extractor.registry.register(extension, extractor);
+    }
+
+    pub fn set_existing_install_policy(&mut self, policy: ExistingInstallPolicy) {
+        self.installer.existing_policy = policy;
+    }
+
+    // setup skips the download and install when install_path already runs a java of the requested
+    // feature version, force reinstalls it anyway
+    pub fn set_force(&mut self, force: bool) {
+        self.force = force;
+    }
+
+    // Unpacks tar.gz packages while they download, without writing the archive or an extract tree.
+    // Zip packages, copy filters and InstallMode::Link keep the regular download, extract, install flow
+    pub fn set_streaming_install(&mut self, streaming_install: bool) {
+        self.streaming_install = streaming_install;
+    }
+
+    // Uses extract_path/<subpath> as the JDK root instead of looking for the directory holding 'bin',
+    // for archives with an unusual layout
+    pub fn set_jdk_root_subpath<P: AsRef<Path>>(&mut self, subpath: Option<P>) {
+        self.installer.jdk_root_subpath = subpath.map(|subpath| subpath.as_ref().to_path_buf());
+    }
+
+    // Top-level entries the extracted JDK root must have. None restores the default (bin, lib,
+    // include and release, no include for JRE packages), an empty list disables the check
+    pub fn set_expected_entries(&mut self, entries: Option<&[&str]>) {
+        self.installer.expected_entries = entries.map(|entries| entries.iter().map(|entry| entry.to_string()).collect());
+    }
+
+    // Lets setup yield to the runtime between zip entries, for GUIs driving it on a single-threaded runtime
+    pub fn set_yield_during_extract(&mut self, yield_during_extract: bool) {
+        self.yield_during_extract = yield_during_extract;
+    }
+
+    // Decides what happens to files appearing twice in the archive, later ones win by default
+    pub fn set_duplicate_entry_policy(&mut self, policy: DuplicateEntryPolicy) {
+        self.extractor.duplicate_policy = policy;
+    }
+
+    // With InstallMode::Link, extract_path is kept and must live outside the download directory
+    pub fn set_install_mode(&mut self, mode: InstallMode) {
+        self.installer.mode = mode;
+    }
+
+    pub fn set_env_backend(&mut self, backend: EnvBackend) {
+        self.env_configurator.backend = backend;
+    }
+
+    // Writes a java (java.bat on Windows) launcher into shim_dir instead of editing PATH
+    pub fn set_launcher_shim_dir<P: AsRef<Path>>(&mut self, shim_dir: Option<P>) {
+        self.env_configurator.shim_dir = shim_dir.map(|dir| dir.as_ref().to_path_buf());
+    }
+
+    // Whether configuring the environment also changes this process's PATH, on by default.
+    // The persistent PATH is updated either way
+    pub fn set_update_current_process(&mut self, update_current_process: bool) {
+        self.env_configurator.update_current_process = update_current_process;
+    }
+
+    // Whether configuring the environment also points JAVA_HOME at install_path, on by default.
+    // An existing JAVA_HOME is overwritten, like an earlier run's
+    pub fn set_configure_java_home(&mut self, configure_java_home: bool) {
+        self.env_configurator.configure_java_home = configure_java_home;
+    }
+
+    // Verifies after configuring that the new java shadows any other JDK on PATH
+    pub fn set_pin_path_order(&mut self, pin_path_order: bool) {
+        self.env_configurator.pin_path_order = pin_path_order;
+    }
+
+    // The filter receives paths relative to the JDK root (e.g. "demo", "lib/src.zip")
+    // and returns false to leave them out of the install
+    pub fn set_copy_filter<F>(&mut self, include: F)
+    where
+        F: Fn(&Path) -> bool + Send + Sync + 'static,
+    {
+        self.installer.copy_filter = Some(Box::new(include));
+    }
+
+    pub fn set_validate_java_home(&mut self, validate_java_home: bool) {
+        self.validate_java_home = validate_java_home;
+    }
+
+    // On by default: setup fails unless the installed java -version reports the requested feature
+    // release. Turn it off to install builds the host can't run, e.g. for another architecture
+    pub fn set_verify_launch(&mut self, verify_launch: bool) {
+        self.verify_launch = verify_launch;
+    }
+
+    // Fails the setup when the installed javac and java can't build and run a HelloWorld
+    pub fn set_verify_toolchain(&mut self, verify_toolchain: bool) {
+        self.verify_toolchain = verify_toolchain;
+    }
+
+    // Installs into a sibling of install_path and runs java -version there first. The previous install
+    // is only replaced once that works, a failed upgrade leaves it untouched
+    pub fn set_verify_before_swap(&mut self, verify_before_swap: bool) {
+        self.verify_before_swap = verify_before_swap;
+    }
+
+    // Also requires the staged install to match a lockfile, every listed file with its hash and
+    // nothing else. Setting one turns on set_verify_before_swap
+    pub fn set_expected_lockfile(&mut self, lockfile: Option<Lockfile>) {
+        self.expected_lockfile = lockfile;
+    }
+
+    // When install_path can't be written (e.g. under Program Files), stages the install and copies
+    // it there from an elevated robocopy. Windows only, otherwise the setup fails with NeedsElevation
+    pub fn set_elevate_on_access_denied(&mut self, elevate_on_access_denied: bool) {
+        self.elevate_on_access_denied = elevate_on_access_denied;
+    }
+
+    // Catches a stale JAVA_HOME left by another tool that no longer points at a JDK
+    fn check_java_home(&self) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let Ok(java_home) = std::env::var("JAVA_HOME") else {
+            info!("JAVA_HOME is not set, skipping validation.");
+            return Ok(());
+        };
+
+        match verify_installation(Path::new(&java_home)) {
+            Ok(version) => {
+                info!("JAVA_HOME {} runs Java {}", java_home, version);
+                Ok(())
+            }
+            Err(e) => Err(Box::new(JavaSetupError::EnvMisconfigured(format!(
+                "JAVA_HOME {} is not a working JDK: {}",
+                java_home, e
+            )))),
+        }
+    }
+
+    // Lets a UI enable the install only when a binary is offered for this platform
+    pub async fn is_available(&self) -> Result<bool, JavaSetupError> {
+        self.downloader.is_available().await
+    }
+
+    // Lists the JDKs on this system, including the ones next to install_path
+    pub fn discover_jdks(&self) -> Vec<DiscoveredJdk> {
+        let install_root = Path::new(&self.installer.install_path)
+            .parent()
+            .unwrap_or(Path::new("."));
+        JdkRegistry::discover_in(&[install_root])
+    }
+
+    pub fn set_write_hash_index(&mut self, write_hash_index: bool) {
+        self.write_hash_index = write_hash_index;
+    }
+
+    // Limits how many threads hash files for the index and integrity checks, 0 means one per CPU
+    pub fn set_hash_threads(&mut self, threads: usize) {
+        self.hash_threads = if threads == 0 { None } else { Some(threads) };
+    }
+
+    // Recomputes the hashes of install_path and reports missing or modified files
+    pub fn verify_install_integrity(&self) -> Result<IntegrityReport, JavaSetupError> {
+        let install_path = Path::new(&self.installer.install_path);
+        let index = HashIndex::read(install_path)
+            .ok_or_else(|| JavaSetupError::HashIndexMissing(install_path.display().to_string()))?;
+        Ok(index.verify_with_threads(install_path, self.hash_threads)?)
+    }
+
+    // Checks an existing install against a lockfile (version, files and optionally hashes), offline
+    pub fn audit(install_path: &Path, lockfile: &Path, verify_hashes: bool) -> Result<AuditReport, JavaSetupError> {
+        let lockfile = Lockfile::read(lockfile)?;
+        Ok(integrity::audit_install(install_path, &lockfile, verify_hashes)?)
+    }
+
+    // Lists the files an uninstall of install_path would delete and the PATH entries it would strip
+    pub fn uninstall_preview(&self) -> Result<UninstallPreview, JavaSetupError> {
+        let install_path = &self.env_configurator.install_path;
+        let jdk_bin_path = InstalledJdk::new(install_path).bin_dir().to_string_lossy().into_owned();
+        let user_path = match self.env_configurator.read_persistent_path(EnvScope::User) {
+            Ok(user_path) => user_path,
+            Err(e) => {
+                warn!("Could not read the User PATH: {}", e);
+                String::new()
+            }
+        };
+        Ok(uninstall::preview(Path::new(install_path), &jdk_bin_path, &user_path)?)
+    }
+
+    // Deletes install_path and undoes the environment changes of setup: the JDK bin leaves the
+    // user's PATH and JAVA_HOME is cleared if it points at the install, or the launcher shim is
+    // removed. Only installs with this crate's manifest are deleted, others fail with
+    // UnmanagedInstall. Whatever is already gone is skipped, so a second run succeeds too.
+    pub fn uninstall(&self) -> Result<UninstallPreview, JavaSetupError> {
+        let preview = self.uninstall_preview()?;
+        let install_path = &preview.install_path;
+        if install_path.symlink_metadata().is_ok() {
+            if !preview.managed {
+                return Err(JavaSetupError::UnmanagedInstall(install_path.display().to_string()));
+            }
+            remove_install_dir(install_path)?;
+            info!("Removed {}", install_path.display());
+        }
+
+        match &self.env_configurator.shim_dir {
+            Some(shim_dir) => {
+                let shim_path = shim_dir.join(if cfg!(windows) { "java.bat" } else { "java" });
+                if shim_path.exists() {
+                    fs::remove_file(&shim_path)?;
+                    info!("Removed the java launcher {}", shim_path.display());
+                }
+            }
+            None => unsafe {
+                self.env_configurator.unconfigure()?;
+            },
+        }
+        Ok(preview)
+    }
+
+    // Checks Adoptium for a newer GA of the feature release installed in install_path
+    pub async fn check_update(&self, install_path: &Path) -> Result<UpdateStatus, JavaSetupError> {
+        // Older manifests lack the release name, the running java still tells the version
+        let installed = match InstallManifest::read(install_path).and_then(|manifest| manifest.release_name) {
+            Some(release_name) => release_name,
+            None => verify_installation(install_path)?,
+        };
+        let installed_version = JavaVersion::parse(&installed)
+            .ok_or_else(|| JavaSetupError::VerificationFailed(format!("unrecognized version {}", installed)))?;
+
+        let latest = self.downloader.resolve_latest(installed_version.feature).await?
+            .and_then(|asset| asset.release_name)
+            .ok_or_else(|| JavaSetupError::AssetNotFound(format!("latest GA of Java {}", installed_version.feature)))?;
+        let latest_version = JavaVersion::parse(&latest)
+            .ok_or_else(|| JavaSetupError::VerificationFailed(format!("unrecognized version {}", latest)))?;
+
+        if latest_version.compare(&installed_version).is_gt() {
+            Ok(UpdateStatus::Available { from: installed, to: latest })
+        } else {
+            Ok(UpdateStatus::UpToDate)
+        }
+    }
+
+    // Copies back only the damaged files from a JDK re-extracted into extract_path
+    pub fn repair_install(&self, report: &IntegrityReport) -> Result<usize, JavaSetupError> {
+        let jdk_root = self.installer.jdk_root(Path::new(&self.extractor.extract_path))
+            .ok_or(JavaSetupError::JdkDirNotFound)?;
+        let install_path = Path::new(&self.installer.install_path);
+
+        let mut repaired = 0;
+        for key in report.damaged() {
+            let source = jdk_root.join(key);
+            let destination = install_path.join(key);
+            if let Some(parent) = destination.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            fs::copy(&source, &destination)?;
+            repaired += 1;
+        }
+        info!("Repaired {} files in {}", repaired, install_path.display());
+        Ok(repaired)
+    }
+
+    // Each package is placed in its own subdirectory of supplementary_path
+    pub fn set_supplementary_packages(&mut self, packages: &[SupplementaryPackage], supplementary_path: &str) {
+        self.supplementary_packages = packages.to_vec();
+        self.supplementary_path = supplementary_path.to_string();
+    }
+
+    // Downloads and places the requested supplementary packages, skipping the ones not offered.
+    // With reused_release (the release of an install setup reused) packages whose manifest
+    // records that release are already in place and left alone
+    async fn install_supplementary(&self, download_dir: &Path, reused_release: Option<&str>) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        for package in &self.supplementary_packages {
+            let destination = Path::new(&self.supplementary_path).join(package.image_type());
+            if let Some(reused_release) = reused_release
+                && InstallManifest::read(&destination).and_then(|manifest| manifest.release_name).as_deref() == Some(reused_release)
+            {
+                info!("The {} package of {} is already in {}, skipping.", package.image_type(), reused_release, destination.display());
+                continue;
+            }
+            let Some(asset) = self.downloader.resolve_supplementary(*package).await? else {
+                info!("No {} package offered for Java {}, skipping.", package.image_type(), &self.downloader.java_version);
+                continue;
+            };
+
+            let archive_path = download_dir.join(&asset.name);
+            self.downloader.download_asset(&asset, &archive_path).await?;
+            if destination.exists() {
+                fs::remove_dir_all(&destination)?;
+            }
+
+            if self.extractor.registry.find(&asset.name).is_some() {
+                let extractor = Extractor {
+                    download_path: archive_path.to_string_lossy().into_owned(),
+                    extract_path: destination.to_string_lossy().into_owned(),
+                    registry: self.extractor.registry.clone(),
+                    duplicate_policy: self.extractor.duplicate_policy,
+                    progress: self.extractor.progress.clone(),
+                    cancel: self.extractor.cancel.clone(),
+                };
+                extractor.extract()?;
+            } else {
+                // Archives the extractor can't handle are placed as-is
+                fs::create_dir_all(&destination)?;
+                fs::copy(&archive_path, destination.join(&asset.name))?;
+            }
+            InstallManifest::new(&self.downloader.java_version, asset.release_name.clone(), Some(asset.name.clone()), asset.checksum.clone())
+                .write(&destination)?;
+            info!("{} package placed in {}", package.image_type(), destination.display());
+            if !self.keep_temp_files
+                && let Err(e) = fs::remove_file(&archive_path)
+            {
+                warn!("Could not delete {}: {}", archive_path.display(), e);
+            }
+        }
+        Ok(())
+    }
+
+    // Opt-in Ctrl-C handler cancelling the setup's CancellationToken, so a running setup removes its
+    // partial download/extract artifacts and fails with Cancelled. The process keeps running, the
+    // caller decides whether to exit. Call it after set_cancel_token, from within a tokio runtime.
+    pub fn install_signal_cleanup(&self) -> tokio::task::JoinHandle<()> {
+        let cancel = self.downloader.cancel.clone();
+        tokio::spawn(async move {
+            if tokio::signal::ctrl_c().await.is_ok() {
+                warn!("Interrupted, cancelling the setup...");
+                cancel.cancel();
+            }
+        })
+    }
+
+    pub async fn setup(&mut self) -> Result<InstallReport, JavaSetupError> {
+        let mut report = InstallReport::default();
+        let installed = if !self.force && self.reuse_existing(&mut report) {
+            Ok(())
+        } else {
+            self.install_only(&mut report).await
+        };
+        let result = match installed {
+            Ok(()) => self.finish(&mut report).await.map(|_| report),
+            Err(e) => Err(e),
+        }
+        .map_err(JavaSetupError::from);
+        self.send_outcome(&result).await;
+        result
+    }
+
+    // Fills report from install_path when it already holds a working install of the requested
+    // feature version and image type. A java that doesn't launch counts as not installed.
+    fn reuse_existing(&self, report: &mut InstallReport) -> bool {
+        let install_path = Path::new(&self.installer.install_path);
+        let Some(requested) = JavaVersion::parse(&self.downloader.java_version) else {
+            return false;
+        };
+        let jdk = InstalledJdk::new(install_path);
+        if !jdk.bin_dir().is_dir() {
+            return false;
+        }
+        // A JRE has to be replaced when the full JDK is requested
+        if self.downloader.image_type == ImageType::Jdk && !jdk.javac_exe().exists() {
+            warn!("{} has no javac, reinstalling.", install_path.display());
+            return false;
+        }
+        let installed = match verify_installation(install_path) {
+            Ok(installed) => installed,
+            Err(e) => {
+                warn!("The existing install doesn't work ({}), reinstalling.", e);
+                return false;
+            }
+        };
+        if JavaVersion::parse(&installed).map(|version| version.feature) != Some(requested.feature) {
+            info!("{} has Java {}, Java {} was requested.", install_path.display(), installed, requested.feature);
+            return false;
+        }
+        let release = JdkRelease::read(&jdk.release_file()).ok().flatten();
+        if release.as_ref().is_some_and(|release| !release.matches_arch(&self.downloader.architecture)) {
+            warn!("{} is built for another architecture, reinstalling.", install_path.display());
+            return false;
+        }
+
+        info!("Java {} is already installed in {}, skipping the download.", installed, install_path.display());
+        report.install_path = install_path.to_path_buf();
+        report.jdk = jdk;
+        apply_release(report, release, &self.downloader.architecture);
+        report.already_installed = true;
+        report.version = Some(installed);
+        true
+    }
+
+    // Tells the event channel how the setup ended
+    async fn send_outcome(&self, result: &Result<InstallReport, JavaSetupError>) {
+        send_outcome(self.events.as_deref(), result).await;
+    }
+
+    // Installs next to the existing versions (KeepVersioned), then repoints current_link at the new
+    // install and configures the environment through the link. Activation only happens once the new
+    // java runs, and a failure after that points current_link back at the previous version.
+    pub async fn install_and_activate<P: AsRef<Path>>(
+        &mut self,
+        current_link: P,
+    ) -> Result<InstallReport, JavaSetupError> {
+        let result = self.activate(current_link.as_ref().to_path_buf()).await.map_err(JavaSetupError::from);
+        self.send_outcome(&result).await;
+        result
+    }
+
+    async fn activate(&mut self, current_link: PathBuf) -> Result<InstallReport, Box<dyn std::error::Error + Send + Sync>> {
+        if Path::new(&self.installer.install_path) == current_link {
+            return Err(Box::new(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                "the current link must not be the install path",
+            )));
+        }
+        let previous = match current_link.symlink_metadata() {
+            // A relative link target is relative to the link's directory
+            Ok(metadata) if metadata.is_symlink() => Some(current_link.parent().unwrap_or(Path::new("")).join(fs::read_link(&current_link)?)),
+            Ok(_) => {
+                return Err(Box::new(std::io::Error::new(
+                    std::io::ErrorKind::AlreadyExists,
+                    format!("{} exists and is not a link", current_link.display()),
+                )));
+            }
+            Err(_) => None,
+        };
+
+        let mut report = InstallReport::default();
+        let existing_policy = std::mem::replace(&mut self.installer.existing_policy, ExistingInstallPolicy::KeepVersioned);
+        let installed = self.install_only(&mut report).await;
+        self.installer.existing_policy = existing_policy;
+        installed?;
+        let new_install = report.install_path.clone();
+        let replaced_previous = previous.as_deref().is_some_and(|previous| same_dir(previous, &new_install));
+        if replaced_previous {
+            warn!("Reinstalled the active version in place, a failure can't be rolled back.");
+        }
+
+        // The environment is configured through the link, a rollback points it back at the old path
+        let previous_env_path = self.env_configurator.install_path.clone();
+        let activated = async {
+            verify_installation(&new_install)?;
+            info!("Activating {} through {}", new_install.display(), current_link.display());
+            point_link(&current_link, &new_install)?;
+            verify_installation(&current_link)?;
+            self.env_configurator.install_path = current_link.to_string_lossy().into_owned();
+            self.finish(&mut report).await
+        }
+        .await;
+
+        if let Err(e) = activated {
+            error!("Activation failed ({}), rolling back...", e);
+            self.env_configurator.install_path = previous_env_path;
+            match &previous {
+                Some(previous) => point_link(&current_link, previous)?,
+                None => remove_install_dir(&current_link)?,
+            }
+            if !replaced_previous {
+                remove_install_dir(&new_install)?;
+            }
+            return Err(e);
+        }
+        report.previous_active = previous;
+        Ok(report)
+    }
+
+    // Resolves, downloads and installs the JDK without touching the environment
+    async fn install_only(&mut self, report: &mut InstallReport) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let installed = self.fetch_and_unpack(report).await;
+        self.cancelled_or(installed)
+    }
+
+    // Whatever failed after a cancel failed because of it, the partial files are removed
+    fn cancelled_or<T>(&mut self, result: Result<T, Box<dyn std::error::Error + Send + Sync>>) -> Result<T, Box<dyn std::error::Error + Send + Sync>> {
+        match result {
+            Err(_) if self.downloader.cancel.is_cancelled() => {
+                warn!("Setup cancelled, removing partial files...");
+                self.cleanup.cleanup();
+                if let Err(e) = self.remove_extracted() {
+                    warn!("Could not delete the extracted files in {}: {}", self.extractor.extract_path, e);
+                }
+                self.remove_temp_files();
+                Err(Box::new(JavaSetupError::Cancelled))
+            }
+            result => result,
+        }
+    }
+
+    async fn fetch_and_unpack(&mut self, report: &mut InstallReport) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let fetched = match self.local_fetched() {
+            Some(fetched) => fetched,
+            None => match self.resolve_for_setup().await? {
+                asset if self.can_stream(&asset) => return self.stream_install(&asset, report).await,
+                asset => self.download_resolved(asset).await?,
+            },
+        };
+        self.check_cancelled()?;
+        if self.yield_during_extract {
+            self.unpack_yielding(&fetched, report).await
+        } else {
+            self.unpack(&fetched, report)
+        }
+    }
+
+    // Installs several versions, downloading up to max_parallel_downloads of the next versions
+    // while the current one is extracted and installed. Results keep the order of setups.
+    // Each version goes through the same steps as setup: an existing install is reused, a cancel
+    // removes the partial files and the event channel gets the outcome.
+    pub async fn install_many(setups: Vec<JavaSetup>, max_parallel_downloads: usize) -> Vec<BatchInstallResult> {
+        // None when the version was already installed and the report filled from it
+        type FetchOutcome = (JavaSetup, InstallReport, Result<Option<FetchedArchive>, Box<dyn std::error::Error + Send + Sync>>, Duration);
+
+        let mut queue = setups.into_iter();
+        let mut pending: VecDeque<(String, tokio::task::JoinHandle<FetchOutcome>)> = VecDeque::new();
+        let mut results = Vec::new();
+
+        let spawn_fetch = |mut setup: JavaSetup| {
+            let java_version = setup.downloader.java_version.clone();
+            let handle = tokio::spawn(async move {
+                let started = Instant::now();
+                let mut report = InstallReport::default();
+                let fetched = if !setup.force && setup.reuse_existing(&mut report) {
+                    Ok(None)
+                } else {
+                    let fetched = setup.fetch().await.map(Some);
+                    setup.cancelled_or(fetched)
+                };
+                (setup, report, fetched, started.elapsed())
+            });
+            (java_version, handle)
+        };
+
+        if let Some(setup) = queue.next() {
+            pending.push_back(spawn_fetch(setup));
+        }
+        while let Some((java_version, handle)) = pending.pop_front() {
+            // Keeps the next downloads running while this version is installed
+            while pending.len() < max_parallel_downloads.max(1) {
+                match queue.next() {
+                    Some(setup) => pending.push_back(spawn_fetch(setup)),
+                    None => break,
+                }
+            }
+
+            let (setup, report, fetched, download_time) = match handle.await {
+                Ok(fetched) => fetched,
+                Err(e) => {
+                    results.push(BatchInstallResult {
+                        java_version,
+                        download_time: Duration::ZERO,
+                        install_time: Duration::ZERO,
+                        result: Err(JavaSetupError::Other(Box::new(e))),
+                    });
+                    continue;
+                }
+            };
+
+            let started = Instant::now();
+            let events = setup.events.clone();
+            let result = match fetched {
+                Ok(fetched) => setup.install_fetched(fetched, report).await,
+                Err(e) => Err(e),
+            }
+            .map_err(JavaSetupError::from);
+            send_outcome(events.as_deref(), &result).await;
+            info!("Java {}: downloaded in {:?}, installed in {:?}", java_version, download_time, started.elapsed());
+            results.push(BatchInstallResult {
+                java_version,
+                download_time,
+                install_time: started.elapsed(),
+                result,
+            });
+        }
+        results
+    }
+
+    // Runs the extract/install stage off the async runtime unless the install was reused, then
+    // configures the environment
+    async fn install_fetched(
+        mut self,
+        fetched: Option<FetchedArchive>,
+        mut report: InstallReport,
+    ) -> Result<InstallReport, Box<dyn std::error::Error + Send + Sync>> {
+        if let Some(fetched) = fetched {
+            let (setup, unpacked_report, unpacked) = tokio::task::spawn_blocking(move || {
+                let unpacked = self.unpack(&fetched, &mut report);
+                (self, report, unpacked)
+            })
+            .await?;
+            self = setup;
+            report = unpacked_report;
+            self.cancelled_or(unpacked)?;
+        }
+        self.finish(&mut report).await?;
+        Ok(report)
+    }
+
+    // Directory holding download_path, or download_path itself when it is a directory
+    fn download_dir(&self) -> PathBuf {
+        let download_path = Path::new(&self.downloader.download_path);
+        if is_dir_path(download_path) {
+            download_path.to_path_buf()
+        } else {
+            download_path.parent().unwrap_or(Path::new(".")).to_path_buf()
+        }
+    }
+
+    // Resolves and downloads the JDK archive
+    async fn fetch(&mut self) -> Result<FetchedArchive, Box<dyn std::error::Error + Send + Sync>> {
+        if let Some(fetched) = self.local_fetched() {
+            return Ok(fetched);
+        }
+        let asset = self.resolve_for_setup().await?;
+        self.download_resolved(asset).await
+    }
+
+    // Describes the archive given to from_archive as if it had been downloaded
+    fn local_fetched(&self) -> Option<FetchedArchive> {
+        let archive_path = self.local_archive.as_ref()?;
+        let name = archive_path.file_name().unwrap_or_default().to_string_lossy().into_owned();
+        // Stands in for the release name, so KeepVersioned installs get a distinct directory
+        let stem = match name.split_once(".tar.") {
+            Some((stem, _)) => stem.to_string(),
+            None => archive_path.file_stem().unwrap_or_default().to_string_lossy().into_owned(),
+        };
+        info!("Installing the local archive {}", archive_path.display());
+        Some(FetchedArchive {
+            asset: ReleaseAsset {
+                release_name: Some(stem),
+                vendor: None,
+                name,
+                link: archive_path.to_string_lossy().into_owned(),
+                size: fs::metadata(archive_path).ok().map(|metadata| metadata.len()),
+                checksum: None,
+            },
+            checksum_verified: false,
+        })
+    }
+
+    // Prepares the download directory, resolves the package and asks for confirmation
+    async fn resolve_for_setup(&mut self) -> Result<ReleaseAsset, Box<dyn std::error::Error + Send + Sync>> {
+        self.cleanup.clear();
+        self.downloaded_archive = None;
+
+        // If the download directory does not exist, create it
+        let download_dir = self.download_dir();
+        if !download_dir.exists() {
+            fs::create_dir_all(&download_dir)?;
+            self.new_download_dir = Some(download_dir);
+        }
+
+        // Fails before downloading anything when the install must not be touched
+        if self.installer.existing_policy == ExistingInstallPolicy::FailIfExists {
+            self.installer.target_path(&self.downloader.java_version)?;
+        }
+
+        info!("Starting download...");
+        self.downloader.progress.stage_started(Stage::Resolve);
+        let asset = self.downloader.resolve().await?
+            .ok_or_else(|| JavaSetupError::DownloadLinkNotFound(self.downloader.java_version.clone()))?;
+        self.downloader.progress.stage_finished(Stage::Resolve);
+        if let Some(confirm) = self.confirm_download.as_mut()
+            && !confirm(&asset)
+        {
+            info!("Download of {} declined.", &asset.name);
+            return Err(Box::new(JavaSetupError::Declined));
+        }
+        Ok(asset)
+    }
+
+    async fn download_resolved(&mut self, asset: ReleaseAsset) -> Result<FetchedArchive, Box<dyn std::error::Error + Send + Sync>> {
+        self.downloader.progress.stage_started(Stage::Download);
+        // Failed requests and dropped transfers are retried by the downloader
+        let downloaded = self.downloader.download(&asset).await?;
+        // The extractor picks the archive format from the real file name
+        self.extractor.download_path = downloaded.path.to_string_lossy().into_owned();
+        self.downloaded_archive = Some(downloaded.path);
+        self.downloader.progress.stage_finished(Stage::Download);
+        Ok(FetchedArchive { asset, checksum_verified: downloaded.checksum_verified })
+    }
+
+    // Extracts and installs the downloaded archive
+    fn unpack(&mut self, fetched: &FetchedArchive, report: &mut InstallReport) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        info!("Extracting...");
+        self.note_extract_dir();
+        let downloader = &self.downloader;
+        let policy = |error: &(dyn std::error::Error + Send + Sync + 'static), attempt| downloader.retry_decision(error, attempt);
+        self.extractor.progress.stage_started(Stage::Extract);
+        let extractor = &self.extractor;
+        retry_blocking(policy, "Extraction", || extractor.extract())?;
+        self.extractor.progress.stage_finished(Stage::Extract);
+        self.install_extracted(fetched, report)
+    }
+
+    // Same as unpack, extracting with Extractor::extract_async
+    async fn unpack_yielding(&mut self, fetched: &FetchedArchive, report: &mut InstallReport) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        info!("Extracting...");
+        self.note_extract_dir();
+        self.extractor.progress.stage_started(Stage::Extract);
+        let mut attempt = 1;
+        loop {
+            match self.extractor.extract_async().await {
+                Ok(()) => break,
+                Err(e) => match self.retry_decision(e.as_ref(), attempt) {
+                    RetryDecision::Retry(delay) => {
+                        warn!("Extraction failed ({}), retrying in {:?}...", e, delay);
+                        tokio::time::sleep(delay).await;
+                        attempt += 1;
+                    }
+                    RetryDecision::Abort => return Err(e),
+                },
+            }
+        }
+        self.extractor.progress.stage_finished(Stage::Extract);
+        self.install_extracted(fetched, report)
+    }
+
+    fn retry_decision(&self, error: &(dyn std::error::Error + Send + Sync + 'static), attempt: u32) -> RetryDecision {
+        self.downloader.retry_decision(error, attempt)
+    }
+
+    // Remembers whether extract_path is created by this extraction or what it held before
+    fn note_extract_dir(&mut self) {
+        let extract_path = Path::new(&self.extractor.extract_path);
+        match extract_path.read_dir() {
+            Ok(entries) => {
+                self.new_extract_dir = None;
+                self.existing_extract_entries = Some(entries.filter_map(|entry| entry.ok().map(|entry| entry.path())).collect());
+            }
+            Err(_) => {
+                self.cleanup.track(extract_path);
+                self.new_extract_dir = Some(extract_path.to_path_buf());
+                self.existing_extract_entries = None;
+            }
+        }
+    }
+
+    // Deletes what the last extraction wrote: extract_path itself when this run created it,
+    // otherwise only the entries that weren't there before
+    fn remove_extracted(&mut self) -> std::io::Result<()> {
+        let written: Vec<PathBuf> = match (self.new_extract_dir.take(), self.existing_extract_entries.take()) {
+            (Some(extract_dir), _) => vec![extract_dir],
+            (None, Some(existing)) => Path::new(&self.extractor.extract_path)
+                .read_dir()?
+                .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+                .filter(|path| !existing.contains(path))
+                .collect(),
+            (None, None) => return Ok(()),
+        };
+        for path in written {
+            let removed = match path.symlink_metadata() {
+                Ok(metadata) if metadata.is_dir() => fs::remove_dir_all(&path),
+                Ok(_) => fs::remove_file(&path),
+                Err(_) => Ok(()),
+            };
+            removed?;
+        }
+        Ok(())
+    }
+
+    // Installs the extracted JDK and records it, then drops the extracted files
+    fn install_extracted(&mut self, fetched: &FetchedArchive, report: &mut InstallReport) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        // The last point a cancel stops the setup, a started install is finished and configured
+        self.check_cancelled()?;
+        let asset = &fetched.asset;
+        report.checksum_verified = fetched.checksum_verified;
+        report.vendor = asset.vendor.clone();
+
+        let downloader = &self.downloader;
+        let policy = |error: &(dyn std::error::Error + Send + Sync + 'static), attempt| downloader.retry_decision(error, attempt);
+        info!("Installing...");
+        self.installer.progress.stage_started(Stage::Install);
+        let version = asset
+            .release_name
+            .clone()
+            .unwrap_or_else(|| self.downloader.java_version.clone());
+        let target = self.installer.target_path(&version)?;
+        self.installer.image_type = image_type_of(&asset.name);
+        // The download directory is deleted at the end, a link into it would dangle
+        let allow_link = !Path::new(&self.extractor.extract_path).starts_with(self.download_dir());
+        if self.installer.mode == InstallMode::Link && !allow_link {
+            warn!("extract_path is inside the download directory, copying instead of linking.");
+        }
+        let staged = self.verify_before_swap || self.expected_lockfile.is_some();
+        let install_dir = if staged { sibling_path(&target, ".staged") } else { target.clone() };
+        if staged {
+            self.cleanup.track(&install_dir);
+        }
+        let installer = &mut self.installer;
+        let (copy_stats, release) = match retry_blocking(policy, "Install", || installer.install(&install_dir, allow_link)) {
+            Ok(copy_stats) => {
+                if staged {
+                    self.verify_staged(&install_dir).inspect_err(|_| {
+                        let _ = remove_install_dir(&install_dir);
+                    })?;
+                    swap_in(&install_dir, &target)?;
+                }
+                (copy_stats, self.record_install(asset, &target)?)
+            }
+            Err(e) if elevation::is_permission_denied(e.as_ref()) => {
+                if !(self.elevate_on_access_denied && elevation::SUPPORTED) {
+                    return Err(needs_elevation(&target, e.as_ref()));
+                }
+                self.install_elevated(asset, &target)?
+            }
+            Err(e) => return Err(e),
+        };
+        report.files_skipped = copy_stats.files_skipped;
+        report.files_retried = copy_stats.files_retried;
+        report.linked = copy_stats.linked;
+        apply_release(report, release, &self.downloader.architecture);
+
+        let extract_path = self.extractor.extract_path.clone();
+        if copy_stats.linked {
+            info!("Keeping extracted files at {}, the install links to them.", extract_path);
+        } else if self.keep_extracted || self.keep_temp_files {
+            info!("Keeping extracted files at {}", extract_path);
+        } else {
+            // The install is complete, leftovers only cost disk space
+            match self.remove_extracted() {
+                Ok(()) => info!("Extracted files deleted."),
+                Err(e) => warn!("Could not delete the extracted files in {}: {}", extract_path, e),
+            }
+        }
+
+        // The environment points at wherever the JDK actually went
+        self.env_configurator.install_path = target.to_string_lossy().into_owned();
+        report.jdk = InstalledJdk::new(&target);
+        report.install_path = target;
+        self.installer.progress.stage_finished(Stage::Install);
+        Ok(())
+    }
+
+    // Builds the install in the download directory, then mirrors it into target from an elevated process
+    fn install_elevated(
+        &mut self,
+        asset: &ReleaseAsset,
+        target: &Path,
+    ) -> Result<(CopyStats, Option<JdkRelease>), Box<dyn std::error::Error + Send + Sync>> {
+        let download_dir = self.download_dir();
+        if !download_dir.exists() && self.new_download_dir.is_none() {
+            self.new_download_dir = Some(download_dir.clone());
+        }
+        let staging = download_dir.join("elevated-install");
+        remove_install_dir(&staging)?;
+        // A link can't be staged, the copy always gets the files themselves
+        let copy_stats = self.installer.install(&staging, false)?;
+        if self.verify_before_swap || self.expected_lockfile.is_some() {
+            self.verify_staged(&staging).inspect_err(|_| {
+                let _ = remove_install_dir(&staging);
+            })?;
+        }
+        let release = self.record_install(asset, &staging)?;
+
+        let copied = self.copy_elevated(&staging, target);
+        remove_install_dir(&staging)?;
+        copied?;
+        Ok((copy_stats, release))
+    }
+
+    // Mirrors a finished install into target from an elevated process
+    fn copy_elevated(&self, staged: &Path, target: &Path) -> Result<(), JavaSetupError> {
+        info!("Access to {} denied, requesting elevation...", target.display());
+        elevation::copy_elevated(staged, target).map_err(|e| JavaSetupError::NeedsElevation {
+            operation: format!("copying the install to {}", target.display()),
+            reason: e.to_string(),
+        })?;
+        info!("JDK installed to {} from an elevated process", target.display());
+        Ok(())
+    }
+
+    // Checks a staged install before it replaces anything: java -version, then the lockfile if one is set
+    fn verify_staged(&self, staged: &Path) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        info!("Verifying the staged install at {}...", staged.display());
+        let Some(lockfile) = &self.expected_lockfile else {
+            let version = verify_installation(staged)?;
+            info!("Staged install runs Java {}.", version);
+            return Ok(());
+        };
+        let audit = integrity::audit_install(staged, lockfile, true)?;
+        if !audit.is_clean() {
+            return Err(Box::new(JavaSetupError::VerificationFailed(format!(
+                "the staged install doesn't match the lockfile (Java {}, expected {}; {} missing, {} modified, {} unexpected files)",
+                audit.actual_version.as_deref().unwrap_or("not runnable"),
+                audit.expected_version,
+                audit.missing.len(),
+                audit.modified.len(),
+                audit.unexpected.len()
+            ))));
+        }
+        info!("Staged install matches the lockfile.");
+        Ok(())
+    }
+
+    // Writes the manifest and, if enabled, the hash index of a finished install, returns its release file
+    fn record_install(
+        &self,
+        asset: &ReleaseAsset,
+        target: &Path,
+    ) -> Result<Option<JdkRelease>, Box<dyn std::error::Error + Send + Sync>> {
+        let mut manifest = InstallManifest::new(
+            &self.downloader.java_version,
+            asset.release_name.clone(),
+            Some(asset.name.clone()),
+            asset.checksum.clone(),
+        );
+        let release = JdkRelease::read(&InstalledJdk::new(target).release_file())?;
+        if let Some(release) = &release {
+            manifest.runtime_version = release.runtime_version.clone().or_else(|| release.java_version.clone());
+            manifest.implementor = release.implementor.clone();
+            manifest.os_arch = release.os_arch.clone();
+        }
+        manifest.write(target)?;
+        if self.write_hash_index {
+            HashIndex::build_with_threads(target, self.hash_threads)?.write(target)?;
+            info!("Hash index written to {}", HashIndex::path(target).display());
+        }
+        Ok(release)
+    }
+
+    fn can_stream(&self, asset: &ReleaseAsset) -> bool {
+        let name = asset.name.to_ascii_lowercase();
+        self.streaming_install
+            && self.downloader.source.is_none()
+            && (name.ends_with(".tar.gz") || name.ends_with(".tgz"))
+            && self.installer.copy_filter.is_none()
+            && self.installer.mode == InstallMode::Copy
+    }
+
+    // Downloads and unpacks in one pass into a staging directory next to the install, which
+    // replaces the install only once the streamed checksum matches
+    async fn stream_install(&mut self, asset: &ReleaseAsset, report: &mut InstallReport) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let version = asset.release_name.clone().unwrap_or_else(|| self.downloader.java_version.clone());
+        let target = self.installer.target_path(&version)?;
+        let expected = self.downloader.expected_checksum(asset)?;
+
+        let staging = sibling_path(&target, ".partial");
+        if let Err(e) = remove_install_dir(&staging).and_then(|()| fs::create_dir_all(&staging)) {
+            if !elevation::is_permission_denied(&e) {
+                return Err(Box::new(e));
+            }
+            if !(self.elevate_on_access_denied && elevation::SUPPORTED) {
+                return Err(needs_elevation(&target, &e));
+            }
+            // The elevated copy needs the extracted files somewhere writable, as without streaming
+            info!("Can't write next to {}, downloading before the elevated install.", target.display());
+            let fetched = self.download_resolved(asset.clone()).await?;
+            return self.unpack(&fetched, report);
+        }
+        self.cleanup.track(&staging);
+
+        debug!("Streaming {} into {}", &asset.link, staging.display());
+        // Download and extraction overlap, both start now and finish once the checksum matched
+        self.downloader.progress.stage_started(Stage::Download);
+        self.downloader.progress.stage_started(Stage::Extract);
+        let (sender, receiver) = tokio::sync::mpsc::channel(streaming::STREAM_BUFFER_CHUNKS);
+        let unpack_dir = staging.clone();
+        let duplicates = self.extractor.duplicate_policy;
+        let progress = self.extractor.progress.clone();
+        let unpacker = tokio::task::spawn_blocking(move || {
+            streaming::unpack_stream(streaming::ChannelReader::new(receiver), &unpack_dir, duplicates, &*progress)
+        });
+        let streamed = self.downloader.stream_asset(asset, sender).await;
+        let unpacked = unpacker.await?;
+
+        // A failed unpack closes the channel, so its error explains a failed stream too
+        let result = match (unpacked, streamed) {
+            (Err(e), _) => Err(e),
+            (Ok(()), Err(e)) => Err(e.into()),
+            (Ok(()), Ok(actual)) => match expected {
+                Some(expected) => Downloader::compare_checksum(asset, expected, actual).map(|()| true),
+                None => Ok(false),
+            },
+        };
+        let checksum_verified = match result {
+            Ok(checksum_verified) => checksum_verified,
+            Err(e) => {
+                remove_install_dir(&staging)?;
+                return Err(e);
+            }
+        };
+
+        self.downloader.progress.stage_finished(Stage::Download);
+        self.downloader.progress.stage_finished(Stage::Extract);
+
+        self.installer.progress.stage_started(Stage::Install);
+        let Some(jdk_root) = self.installer.jdk_root(&staging) else {
+            remove_install_dir(&staging)?;
+            return Err(Box::new(JavaSetupError::JdkDirNotFound));
+        };
+        self.installer.image_type = image_type_of(&asset.name);
+        let checked = match self.installer.check_layout(&jdk_root) {
+            Ok(()) if self.verify_before_swap || self.expected_lockfile.is_some() => self.verify_staged(&jdk_root),
+            checked => checked,
+        };
+        if let Err(e) = checked {
+            remove_install_dir(&staging)?;
+            return Err(e);
+        }
+        let release = match swap_in(&jdk_root, &target) {
+            Ok(()) => {
+                remove_install_dir(&staging)?;
+                info!("JDK installed to {}", target.display());
+                self.record_install(asset, &target)?
+            }
+            Err(e) if elevation::is_permission_denied(&e) => {
+                if !(self.elevate_on_access_denied && elevation::SUPPORTED) {
+                    remove_install_dir(&staging)?;
+                    return Err(needs_elevation(&target, &e));
+                }
+                let installed = self
+                    .record_install(asset, &jdk_root)
+                    .and_then(|release| Ok(self.copy_elevated(&jdk_root, &target).map(|()| release)?));
+                remove_install_dir(&staging)?;
+                installed?
+            }
+            Err(e) => {
+                remove_install_dir(&staging)?;
+                return Err(Box::new(e));
+            }
+        };
+        report.checksum_verified = checksum_verified;
+        report.vendor = asset.vendor.clone();
+        apply_release(report, release, &self.downloader.architecture);
+        self.env_configurator.install_path = target.to_string_lossy().into_owned();
+        report.jdk = InstalledJdk::new(&target);
+        report.install_path = target;
+        self.installer.progress.stage_finished(Stage::Install);
+        Ok(())
+    }
+
+    // Runs java -version in install_path, a copy that came out partial or a build for another
+    // architecture fails here instead of the first time the JDK is used
+    fn check_launches(&self, install_path: &Path) -> Result<String, JavaSetupError> {
+        info!("Checking that the installed java runs...");
+        let installed = verify_installation(install_path)?;
+        // from_archive has no requested version to compare with
+        if let Some(requested) = JavaVersion::parse(&self.downloader.java_version)
+            && JavaVersion::parse(&installed).map(|version| version.feature) != Some(requested.feature)
+        {
+            return Err(JavaSetupError::VerificationFailed(format!(
+                "{} runs Java {}, Java {} was requested",
+                install_path.display(),
+                installed,
+                requested.feature
+            )));
+        }
+        info!("The installed java runs, version {}.", installed);
+        Ok(installed)
+    }
+
+    // Verifies the install, installs the supplementary packages, configures the environment and
+    // removes temporary files
+    async fn finish(&mut self, report: &mut InstallReport) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let download_dir = self.download_dir();
+        let progress = self.downloader.progress.clone();
+        if self.verify_launch || self.verify_toolchain {
+            progress.stage_started(Stage::Verify);
+            // An install reused by setup already ran
+            if self.verify_launch && report.version.is_none() {
+                report.version = Some(self.check_launches(&report.install_path)?);
+            }
+            if self.verify_toolchain {
+                info!("Compiling and running HelloWorld...");
+                report.toolchain_verified = verify_toolchain(&report.install_path)?;
+            }
+            progress.stage_finished(Stage::Verify);
+        }
+        if !self.supplementary_packages.is_empty() {
+            info!("Installing supplementary packages...");
+            let reused_release = report.already_installed
+                .then(|| InstallManifest::read(&report.install_path).and_then(|manifest| manifest.release_name))
+                .flatten();
+            self.install_supplementary(&download_dir, reused_release.as_deref()).await?;
+        }
+
+        progress.stage_started(Stage::Configure);
+        if let Some(shim_dir) = &self.env_configurator.shim_dir {
+            info!("Writing the java launcher instead of changing PATH...");
+            report.launcher_shim = Some(self.env_configurator.write_launcher_shim(shim_dir)?);
+        } else {
+            info!("Configuring environment variables...");
+            unsafe {
+                report.path_already_configured = self.env_configurator.configure()?;
+                if self.env_configurator.pin_path_order {
+                    let verification = self.env_configurator.pin_path_order()?;
+                    if verification.verified {
+                        info!("The installed java is the one resolved from PATH.");
+                    } else {
+                        warn!("Another java still shadows the installed one: {:?}", verification.resolved_java);
+                    }
+                    report.path_order = Some(verification);
+                }
+            }
+        }
+        if self.validate_java_home {
+            self.check_java_home()?;
+        }
+        progress.stage_finished(Stage::Configure);
+
+        progress.stage_started(Stage::Cleanup);
+        if self.keep_temp_files {
+            info!("Done! Keeping the temporary files in {}", download_dir.display());
+        } else {
+            info!("Done! Deleting temporary files...");
+            self.remove_temp_files();
+        }
+        self.cleanup.clear();
+        progress.stage_finished(Stage::Cleanup);
+        Ok(())
+    }
+
+    fn check_cancelled(&self) -> Result<(), JavaSetupError> {
+        if self.downloader.cancel.is_cancelled() { Err(JavaSetupError::Cancelled) } else { Ok(()) }
+    }
+
+    // Deletes only what this run created: the downloaded archive, then the download directory if
+    // the run created it and it is empty now. download_path may be a shared folder, so nothing else
+    // in it is touched. Failures are logged, the install itself already succeeded
+    fn remove_temp_files(&mut self) {
+        let mut removed = false;
+        if let Some(archive) = self.downloaded_archive.take() {
+            match fs::remove_file(&archive) {
+                Ok(()) => removed = true,
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+                Err(e) => warn!("Could not delete {}: {}", archive.display(), e),
+            }
+        }
+        if let Some(download_dir) = self.new_download_dir.take() {
+            match fs::remove_dir(&download_dir) {
+                Ok(()) => removed = true,
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+                Err(_) if download_dir.read_dir().is_ok_and(|mut entries| entries.next().is_some()) => {
+                    info!("Leaving {}, it has files this setup didn't create.", download_dir.display());
+                }
+                Err(e) => warn!("Could not delete {}: {}", download_dir.display(), e),
+            }
+        }
+        if removed {
+            info!("Temporary files deleted.");
+        } else {
+            info!("No temporary files to delete.");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(windows)]
+    #[test]
+    fn windows_path_entries_ignore_case_and_trailing_separators() {
+        assert!(same_path_entry("C:\\Java\\jdk-17\\bin", "c:\\java\\JDK-17\\BIN"));
+        assert!(same_path_entry("C:\\Java\\jdk-17\\bin\\", "C:\\Java\\jdk-17\\bin"));
+        assert!(!same_path_entry("C:\\Java\\jdk-17\\bin", "C:\\Java\\jdk-21\\bin"));
+
+        let path_var = "C:\\Windows\\system32;C:\\Java\\JDK-17\\bin\\;C:\\Tools";
+        assert!(path_contains_entry(path_var, "c:\\java\\jdk-17\\bin"));
+        assert!(path_contains_entry(path_var, "C:\\Tools\\"));
+        assert!(!path_contains_entry(path_var, "C:\\Java\\jdk-17"));
+        assert!(!path_contains_entry("", "C:\\Tools"));
+    }
+
+    #[cfg(not(windows))]
+    #[test]
+    fn unix_path_entries_are_case_sensitive() {
+        assert!(same_path_entry("/opt/jdk-17/bin/", "/opt/jdk-17/bin"));
+        assert!(!same_path_entry("/opt/JDK-17/bin", "/opt/jdk-17/bin"));
+
+        let path_var = "/usr/local/bin:/opt/jdk-17/bin/:/usr/bin";
+        assert!(path_contains_entry(path_var, "/opt/jdk-17/bin"));
+        assert!(path_contains_entry(path_var, "/usr/bin"));
+        assert!(!path_contains_entry(path_var, "/opt/JDK-17/bin"));
+        // ';' is not a separator here, so the whole string is one entry
+        assert!(!path_contains_entry("/usr/bin;/opt/jdk-17/bin", "/opt/jdk-17/bin"));
+    }
+
+    fn rate_limited_after(retry_after: Option<Duration>) -> JavaSetupError {
+        JavaSetupError::RateLimited { url: "https://api.adoptium.net/v3/assets".to_string(), retry_after }
+    }
+
+    #[test]
+    fn backoff_doubles_the_delay_and_gives_up_after_max_attempts() {
+        let error = std::io::Error::from(std::io::ErrorKind::TimedOut);
+        let base = Duration::from_millis(500);
+        assert_eq!(backoff_retry_policy(&error, 1, 3, base), RetryDecision::Retry(Duration::from_millis(500)));
+        assert_eq!(backoff_retry_policy(&error, 2, 3, base), RetryDecision::Retry(Duration::from_secs(1)));
+        assert_eq!(backoff_retry_policy(&error, 3, 3, base), RetryDecision::Abort);
+
+        let error = std::io::Error::from(std::io::ErrorKind::NotFound);
+        assert_eq!(backoff_retry_policy(&error, 1, 3, base), RetryDecision::Abort);
+    }
+
+    #[test]
+    fn rate_limited_requests_wait_for_retry_after() {
+        let base = Duration::from_millis(500);
+        let error = rate_limited_after(Some(Duration::from_secs(7)));
+        assert_eq!(backoff_retry_policy(&error, 1, 3, base), RetryDecision::Retry(Duration::from_secs(7)));
+        assert_eq!(backoff_retry_policy(&error, 3, 3, base), RetryDecision::Abort);
+
+        // Without a usable header the usual backoff applies
+        let error = rate_limited_after(None);
+        assert_eq!(backoff_retry_policy(&error, 2, 3, base), RetryDecision::Retry(Duration::from_secs(1)));
+
+        let error = rate_limited_after(Some(Duration::from_secs(3_600)));
+        assert_eq!(backoff_retry_policy(&error, 1, 3, base), RetryDecision::Retry(MAX_RETRY_AFTER));
+    }
+
+    #[test]
+    fn retry_after_accepts_seconds_and_http_dates() {
+        // Sun, 06 Nov 1994 08:49:37 GMT
+        let now = std::time::UNIX_EPOCH + Duration::from_secs(784_111_777);
+        assert_eq!(parse_retry_after("120", now), Some(Duration::from_secs(120)));
+        assert_eq!(parse_retry_after(" 0 ", now), Some(Duration::ZERO));
+        assert_eq!(parse_retry_after("Sun, 06 Nov 1994 08:50:07 GMT", now), Some(Duration::from_secs(30)));
+        assert_eq!(parse_retry_after("Mon, 07 Nov 1994 08:49:37 GMT", now), Some(Duration::from_secs(86_400)));
+        assert_eq!(parse_retry_after("Sun, 06 Nov 1994 08:00:00 GMT", now), Some(Duration::ZERO));
+        assert_eq!(parse_retry_after("Thu, 29 Feb 2024 00:00:00 GMT", std::time::UNIX_EPOCH + Duration::from_secs(1_709_164_800)), Some(Duration::ZERO));
+
+        assert_eq!(parse_retry_after("-5", now), None);
+        assert_eq!(parse_retry_after("soon", now), None);
+        assert_eq!(parse_retry_after("Sun, 06 Nov 1994 08:50:07 UTC", now), None);
+        assert_eq!(parse_retry_after("Sun, 06 Foo 1994 08:50:07 GMT", now), None);
+        assert_eq!(parse_retry_after("Sun, 06 Nov 1994 25:50:07 GMT", now), None);
+    }
+
+    // A JDK-like tar.gz: the expected top-level entries and files files under lib
+    fn jdk_tar_gz(files: usize) -> Vec<u8> {
+        let encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::fast());
+        let mut builder = tar::Builder::new(encoder);
+        let mut append = |name: &str, content: &[u8]| {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(content.len() as u64);
+            header.set_mode(0o755);
+            header.set_cksum();
+            builder.append_data(&mut header, name, content).unwrap();
+        };
+        append("jdk-17.0.9+9/bin/java", b"#!/bin/sh\n");
+        append("jdk-17.0.9+9/include/jni.h", b"");
+        append("jdk-17.0.9+9/release", b"JAVA_VERSION=\"17.0.9\"\n");
+        for i in 0..files {
+            // Barely compressible, like the class files in lib/modules
+            let content: Vec<u8> = (0..16 * 1024u32).map(|n| (n.wrapping_mul(2_654_435_761).wrapping_add(i as u32) >> 13) as u8).collect();
+            append(&format!("jdk-17.0.9+9/lib/file{}", i), &content);
+        }
+        builder.into_inner().unwrap().finish().unwrap()
+    }
+
+    // Serves body to every GET on a local port until the test process exits, returns its URL
+    fn serve(body: Vec<u8>) -> String {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/OpenJDK17U-jdk_x64_linux.tar.gz", listener.local_addr().unwrap());
+        std::thread::spawn(move || {
+            for stream in listener.incoming() {
+                let Ok(mut stream) = stream else { continue };
+                let mut request = Vec::new();
+                let mut buf = [0u8; 1024];
+                while !request.ends_with(b"\r\n\r\n") {
+                    match std::io::Read::read(&mut stream, &mut buf) {
+                        Ok(0) | Err(_) => break,
+                        Ok(n) => request.extend_from_slice(&buf[..n]),
+                    }
+                }
+                let header = format!("HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n", body.len());
+                let _ = stream.write_all(header.as_bytes()).and_then(|()| stream.write_all(&body));
+            }
+        });
+        url
+    }
+
+    // Run with cargo test --release -- --ignored --nocapture
+    #[tokio::test(flavor = "multi_thread")]
+    #[ignore]
+    async fn bench_streaming_against_download_then_extract() {
+        use sha2::Digest;
+
+        let archive = jdk_tar_gz(4_000);
+        let checksum = format!("{:x}", sha2::Sha256::digest(&archive));
+        let archive_len = archive.len() as u64;
+        let url = serve(archive);
+        let metadata = serde_json::json!([{
+            "release_name": "jdk-17.0.9+9",
+            "binaries": [{ "package": { "name": "OpenJDK17U-jdk_x64_linux.tar.gz", "link": url, "checksum": checksum } }]
+        }]);
+
+        for streaming in [false, true, false, true] {
+            let dir = test_util::TempDir::new("stream-bench");
+            let path = |name: &str| dir.path().join(name).to_string_lossy().into_owned();
+            let mut setup = JavaSetup::new("17", &format!("{}/", path("download")), &path("extract"), &path("jdk"));
+            setup.set_metadata(metadata.clone());
+            setup.set_streaming_install(streaming);
+
+            let started = Instant::now();
+            setup.install_only(&mut InstallReport::default()).await.unwrap();
+            let elapsed = started.elapsed();
+
+            let installed: u64 = WalkDir::new(dir.path().join("jdk"))
+                .into_iter()
+                .filter_map(|entry| entry.ok())
+                .filter_map(|entry| entry.metadata().ok())
+                .filter(|metadata| metadata.is_file())
+                .map(|metadata| metadata.len())
+                .sum();
+            // Download-then-extract writes the archive, the extracted tree and the install
+            let written = if streaming { installed } else { archive_len + 2 * installed };
+            let label = if streaming { "streaming" } else { "three-pass" };
+            println!("{:<10} {:>8.1?}  {:>5} MiB written", label, elapsed, written / (1024 * 1024));
+        }
+    }
+
+    #[cfg(not(windows))]
+    #[tokio::test]
+    async fn missing_archive_fails_without_waiting_for_antivirus() {
+        let dir = test_util::TempDir::new("missing-archive");
+        let path = |name: &str| dir.path().join(name).to_string_lossy().into_owned();
+        let setup = JavaSetup::new("17", &path("jdk.zip"), &path("extract"), &path("jdk"));
+        assert!(!is_quarantine_error(&std::io::Error::from(std::io::ErrorKind::NotFound)));
+
+        let started = Instant::now();
+        let error = setup.extractor.extract_async().await.unwrap_err();
+        assert!(started.elapsed() < QUARANTINE_RETRY_DELAY);
+        let error = error.downcast::<std::io::Error>().expect("a plain io error, not FileQuarantined");
+        assert_eq!(error.kind(), std::io::ErrorKind::NotFound);
+    }
+
+    #[test]
+    fn jdk_root_of_jre_layouts() {
+        // JRE packages without a top-level folder, only bin/java and no javac
+        let flat = test_util::TempDir::new("jre-flat");
+        test_util::write_file(flat.path(), "bin/java", "");
+        test_util::write_file(flat.path(), "lib/modules", "");
+        assert_eq!(find_jdk_root(flat.path()), Some(flat.path().to_path_buf()));
+
+        let nested = test_util::TempDir::new("jre-nested");
+        test_util::write_file(nested.path(), "jdk-17.0.9+9-jre/bin/java", "");
+        test_util::write_file(nested.path(), "jdk-17.0.9+9-jre/release", "");
+        assert_eq!(find_jdk_root(nested.path()), Some(nested.path().join("jdk-17.0.9+9-jre")));
+    }
+
+    #[test]
+    fn jdk_root_of_macos_bundle() {
+        let dir = test_util::TempDir::new("jdk-bundle");
+        test_util::write_file(dir.path(), "jdk-17.0.9+9-jre/Contents/Info.plist", "");
+        test_util::write_file(dir.path(), "jdk-17.0.9+9-jre/Contents/MacOS/libjli.dylib", "");
+        test_util::write_file(dir.path(), "jdk-17.0.9+9-jre/Contents/Home/bin/java", "");
+        assert_eq!(find_jdk_root(dir.path()), Some(dir.path().join("jdk-17.0.9+9-jre/Contents/Home")));
+
+        let empty = test_util::TempDir::new("jdk-none");
+        test_util::write_file(empty.path(), "jdk-17.0.9+9/lib/modules", "");
+        assert_eq!(find_jdk_root(empty.path()), None);
+    }
+
+    // The only test changing HOME and SHELL, everything runs in it so no other test sees them
+    #[cfg(not(windows))]
+    #[test]
+    fn profile_configure_round_trip() {
+        let home = test_util::TempDir::new("profile-home");
+        let profile = "# ~/.profile\nexport EDITOR=vi\n";
+        let zshrc = "alias ll='ls -l'";
+        test_util::write_file(home.path(), ".profile", profile);
+        test_util::write_file(home.path(), ".zshrc", zshrc);
+
+        let saved: Vec<_> = ["HOME", "SHELL"].into_iter().map(|name| (name, std::env::var_os(name))).collect();
+        unsafe {
+            std::env::set_var("HOME", home.path());
+            std::env::set_var("SHELL", "/bin/zsh");
+        }
+        let configurator = EnvironmentVariableConfigurator {
+            install_path: home.path().join("jdk-17").to_string_lossy().into_owned(),
+            pin_path_order: false,
+            backend: EnvBackend::Profile,
+            shim_dir: None,
+            update_current_process: false,
+            configure_java_home: true,
+        };
+        let configured = unsafe { configurator.configure() }.map(|_| ());
+        let configured_profile = fs::read_to_string(home.path().join(".profile"));
+        let unconfigured = unsafe { configurator.unconfigure() };
+        let unconfigured_profile = fs::read_to_string(home.path().join(".profile"));
+
+        // Uninstalling a managed install removes it and its profile lines, a second run has nothing left to do
+        let path = |name: &str| home.path().join(name).to_string_lossy().into_owned();
+        let install_path = home.path().join("jdk-21");
+        test_util::write_file(&install_path, "bin/java", "java");
+        InstallManifest::new("21", None, None, None).write(&install_path).unwrap();
+        let mut setup = JavaSetup::new("21", &path("download/"), &path("extract"), &path("jdk-21"));
+        setup.set_update_current_process(false);
+        let installed = unsafe { setup.env_configurator.configure() }.map(|_| ());
+        let installed_profile = fs::read_to_string(home.path().join(".profile"));
+        let uninstalled = setup.uninstall().map(|_| ());
+        let uninstalled_profile = fs::read_to_string(home.path().join(".profile"));
+        let uninstalled_again = setup.uninstall().map(|_| ());
+        for (name, value) in saved {
+            unsafe {
+                match value {
+                    Some(value) => std::env::set_var(name, value),
+                    None => std::env::remove_var(name),
+                }
+            }
+        }
+
+        configured.unwrap();
+        let configured_profile = configured_profile.unwrap();
+        assert!(configured_profile.contains("export PATH="));
+        assert!(configured_profile.contains("export JAVA_HOME="));
+        unconfigured.unwrap();
+        assert_eq!(unconfigured_profile.unwrap(), profile);
+        assert_eq!(fs::read_to_string(home.path().join(".zshrc")).unwrap(), zshrc);
+
+        let jdk_bin = InstalledJdk::new(&install_path).bin_dir().to_string_lossy().into_owned();
+        installed.unwrap();
+        assert!(installed_profile.unwrap().contains(&jdk_bin));
+        uninstalled.unwrap();
+        assert!(!install_path.exists());
+        assert!(!uninstalled_profile.unwrap().contains(&jdk_bin));
+        uninstalled_again.unwrap();
+    }
+
+    // A java that prints the version line of a 17.0.9 build, a javac beside it
+    #[cfg(unix)]
+    fn fake_jdk(root: &Path) {
+        use std::os::unix::fs::PermissionsExt;
+        test_util::write_file(root, "bin/java", "#!/bin/sh\necho 'openjdk version \"17.0.9\" 2023-10-17' >&2\n");
+        test_util::write_file(root, "bin/javac", "");
+        fs::set_permissions(root.join("bin/java"), fs::Permissions::from_mode(0o755)).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn launch_check_reports_the_printed_version() {
+        let dir = test_util::TempDir::new("launch-check");
+        let install_path = dir.path().join("jdk");
+        fake_jdk(&install_path);
+        let path = |name: &str| dir.path().join(name).to_string_lossy().into_owned();
+
+        let setup = JavaSetup::new("17", &path("download/"), &path("extract"), &path("jdk"));
+        assert_eq!(setup.check_launches(&install_path).unwrap(), "17.0.9");
+        let mut report = InstallReport::default();
+        assert!(setup.reuse_existing(&mut report));
+        assert_eq!(report.version.as_deref(), Some("17.0.9"));
+
+        let setup = JavaSetup::new("21", &path("download/"), &path("extract"), &path("jdk"));
+        assert!(matches!(setup.check_launches(&install_path), Err(JavaSetupError::VerificationFailed(_))));
+    }
+
+    #[test]
+    fn invalid_proxy_keeps_the_previous_client() {
+        let mut setup = JavaSetup::new("17", "download/", "extract", "jdk");
+        setup.set_proxy(Some("http://127.0.0.1:3128")).unwrap();
+        let error = setup.set_proxy(Some("not a proxy url")).unwrap_err();
+        assert!(matches!(&error, JavaSetupError::InvalidProxy(url) if url == "not a proxy url"));
+        assert_eq!(setup.downloader.proxy.as_deref(), Some("http://127.0.0.1:3128"));
+    }
+
+    #[tokio::test]
+    async fn proxy_client_keeps_the_certificate_pins() {
+        // Answers one request with an empty release list and hands back its request line
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let proxy_url = format!("http://{}", listener.local_addr().unwrap());
+        let proxy = std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut request = String::new();
+            std::io::BufRead::read_line(&mut std::io::BufReader::new(&stream), &mut request).unwrap();
+            stream.write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 2\r\nConnection: close\r\n\r\n[]").unwrap();
+            request
+        });
+
+        let mut setup = JavaSetup::new("17", "download/", "extract", "jdk");
+        let fingerprint = "ab".repeat(32);
+        setup.pin_cert(&fingerprint).unwrap();
+        setup.set_proxy(Some(&proxy_url)).unwrap();
+        assert_eq!(setup.downloader.pinned_certs, vec![[0xab; 32]]);
+        assert_eq!(setup.downloader.proxy.as_deref(), Some(proxy_url.as_str()));
+
+        // Plain http needs no pin, the request only shows the rebuilt client goes through the proxy
+        let response = setup.downloader.get_metadata("http://releases.invalid/v3/assets").await.unwrap();
+        assert_eq!(response.text().await.unwrap(), "[]");
+        assert!(proxy.join().unwrap().starts_with("GET http://releases.invalid/v3/assets "));
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn install_many_reuses_existing_installs_and_reports_the_outcome() {
+        let dir = test_util::TempDir::new("batch-reuse");
+        fake_jdk(&dir.path().join("jdk"));
+        let path = |name: &str| dir.path().join(name).to_string_lossy().into_owned();
+        let mut setup = JavaSetup::new("17", &path("download/"), &path("extract"), &path("jdk"));
+        // Nothing to download, and a launcher instead of touching the real profiles
+        setup.set_metadata(serde_json::json!([]));
+        setup.set_launcher_shim_dir(Some(dir.path().join("shims")));
+        let (sender, mut receiver) = tokio::sync::mpsc::channel(64);
+        setup.set_event_sender(sender);
+
+        let results = JavaSetup::install_many(vec![setup], 1).await;
+        let report = results[0].result.as_ref().unwrap();
+        assert!(report.already_installed);
+        assert_eq!(report.version.as_deref(), Some("17.0.9"));
+
+        let mut last = None;
+        while let Ok(event) = receiver.try_recv() {
+            last = Some(event);
+        }
+        assert!(matches!(last, Some(InstallEvent::Completed { install_path }) if install_path == dir.path().join("jdk")));
+    }
+
+    #[tokio::test]
+    async fn cancel_stops_a_stalled_metadata_body() {
+        // Sends the headers and the start of the body, then stalls until the client hangs up
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/v3/assets", listener.local_addr().unwrap());
+        std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut request = String::new();
+            std::io::BufRead::read_line(&mut std::io::BufReader::new(&stream), &mut request).unwrap();
+            stream.write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 1000\r\n\r\n[").unwrap();
+            let _ = std::io::Read::read(&mut stream, &mut [0u8; 1]);
+        });
+
+        let setup = JavaSetup::new("17", "download/", "extract", "jdk");
+        let response = setup.downloader.get_metadata(&url).await.unwrap();
+        let cancel = setup.downloader.cancel.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(100)).await;
+            cancel.cancel();
+        });
+        let read = tokio::time::timeout(Duration::from_secs(5), setup.downloader.read_metadata(response)).await;
+        let error = read.expect("the cancel ends the read").unwrap_err();
+        assert!(matches!(error.downcast_ref::<JavaSetupError>(), Some(JavaSetupError::Cancelled)));
+    }
+
+    #[test]
+    fn access_denied_is_not_retried() {
+        let denied = std::io::Error::from(std::io::ErrorKind::PermissionDenied);
+        assert!(!is_transient_io_error(&denied));
+        assert!(!is_transient_io_error(&std::io::Error::from_raw_os_error(5)));
+        assert_eq!(backoff_retry_policy(&denied, 1, 3, Duration::from_millis(500)), RetryDecision::Abort);
+        assert_eq!(is_transient_io_error(&std::io::Error::from_raw_os_error(32)), cfg!(windows));
+    }
+
+    #[test]
+    fn only_adoptium_lists_the_architectures_of_a_release() {
+        let query = PackageQuery { java_version: "17", image_type: "jdk", os: "linux", architecture: "sparcv9" };
+        for vendor in [Vendor::Corretto, Vendor::Zulu, Vendor::GraalVm] {
+            assert_eq!(vendor.api().architectures_url(&query), None);
+        }
+        let api = Vendor::Adoptium.api();
+        assert!(api.architectures_url(&query).unwrap().starts_with("https://api.adoptium.net/"));
+        let json = serde_json::json!([
+            { "binaries": [{ "architecture": "x64" }, { "architecture": "aarch64" }] },
+            { "binaries": [{ "architecture": "x64" }] }
+        ]);
+        assert_eq!(api.architectures(&json), vec!["aarch64".to_string(), "x64".to_string()]);
+    }
+
+    #[test]
+    fn version_is_found_after_picked_up_lines() {
+        let stderr = "Picked up JAVA_TOOL_OPTIONS: -Dfile.encoding=UTF-8\n\
+            openjdk version \"17.0.9\" 2023-10-17\n\
+            OpenJDK Runtime Environment Temurin-17.0.9+9 (build 17.0.9+9)\n";
+        assert_eq!(parse_version_output(stderr).as_deref(), Some("17.0.9"));
+        assert_eq!(parse_version_output("Picked up _JAVA_OPTIONS: \"-Xmx1g\"\n"), None);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn reused_install_keeps_supplementary_packages_of_its_release() {
+        let dir = test_util::TempDir::new("reuse-supplementary");
+        let install_path = dir.path().join("jdk");
+        fake_jdk(&install_path);
+        let manifest = InstallManifest::new("17", Some("jdk-17.0.9+9".to_string()), None, None);
+        manifest.write(&install_path).unwrap();
+        let sources = dir.path().join("supplementary/sources");
+        test_util::write_file(&sources, "src.zip", "sources");
+        manifest.write(&sources).unwrap();
+
+        let path = |name: &str| dir.path().join(name).to_string_lossy().into_owned();
+        let mut setup = JavaSetup::new("17", &path("download/"), &path("extract"), &path("jdk"));
+        setup.set_launcher_shim_dir(Some(dir.path().join("shims")));
+        setup.set_supplementary_packages(&[SupplementaryPackage::Sources], &path("supplementary"));
+
+        // Resolving the package would need the network, the skip is what lets this succeed
+        let report = setup.setup().await.unwrap();
+        assert!(report.already_installed);
+        assert_eq!(fs::read_to_string(sources.join("src.zip")).unwrap(), "sources");
+    }
+
+    #[tokio::test]
+    async fn only_the_extracted_files_are_deleted_from_an_existing_extract_dir() {
+        let dir = test_util::TempDir::new("extract-existing");
+        let archive = dir.path().join("OpenJDK17U-jdk_x64_linux.tar.gz");
+        fs::write(&archive, jdk_tar_gz(1)).unwrap();
+        let path = |name: &str| dir.path().join(name).to_string_lossy().into_owned();
+        test_util::write_file(&dir.path().join("shared"), "notes.txt", "keep me");
+
+        for extract in ["shared", "fresh"] {
+            let jdk = format!("jdk-{}", extract);
+            let mut setup = JavaSetup::from_archive(&archive, &path(extract), &path(&jdk)).unwrap();
+            setup.install_only(&mut InstallReport::default()).await.unwrap();
+            assert!(dir.path().join(&jdk).join("release").is_file());
+        }
+        let shared: Vec<_> = fs::read_dir(dir.path().join("shared")).unwrap().map(|entry| entry.unwrap().file_name()).collect();
+        assert_eq!(shared, vec![std::ffi::OsString::from("notes.txt")]);
+        assert!(!dir.path().join("fresh").exists());
+    }
+}
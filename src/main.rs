@@ -1,8 +1,32 @@
-mod lib;
-use lib::JavaSetup;
+use launcher_java_installer::JavaSetup;
+
+// Prints the library's log to stdout, debug messages (API responses, PATH contents) included
+// only when JAVA_SETUP_DEBUG is set
+struct StdoutLogger;
+
+impl log::Log for StdoutLogger {
+    fn enabled(&self, metadata: &log::Metadata) -> bool {
+        metadata.level() <= log::max_level()
+    }
+
+    fn log(&self, record: &log::Record) {
+        if self.enabled(record.metadata()) {
+            println!("{}", record.args());
+        }
+    }
+
+    fn flush(&self) {}
+}
 
 #[tokio::main]
 async fn main() {
+    let _ = log::set_logger(&StdoutLogger);
+    log::set_max_level(if std::env::var_os("JAVA_SETUP_DEBUG").is_some() {
+        log::LevelFilter::Debug
+    } else {
+        log::LevelFilter::Info
+    });
+
     // Example usage
     let java_version = "17";
     let download_path = "E:\\Escritorio\\temp\\java_download.zip";
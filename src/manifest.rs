@@ -0,0 +1,129 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use log::warn;
+
+// Name of the manifest file written at the root of every install
+pub const MANIFEST_FILE: &str = ".java-setup-manifest.json";
+
+const WRITE_ATTEMPTS: u32 = 3;
+
+// Records what was installed in install_path, used to recognize and remove installs later
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InstallManifest {
+    pub java_version: String,
+    // Full release installed, e.g. jdk-17.0.9+9, None for manifests written before it was recorded
+    pub release_name: Option<String>,
+    pub asset_name: Option<String>,
+    pub checksum: Option<String>,
+    // Read from the JDK's release file, None when it has none
+    pub runtime_version: Option<String>,
+    pub implementor: Option<String>,
+    pub os_arch: Option<String>,
+    pub installed_at: u64,
+}
+
+impl InstallManifest {
+    pub fn new(java_version: &str, release_name: Option<String>, asset_name: Option<String>, checksum: Option<String>) -> Self {
+        let installed_at = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|elapsed| elapsed.as_secs())
+            .unwrap_or(0);
+        InstallManifest {
+            java_version: java_version.to_string(),
+            release_name,
+            asset_name,
+            checksum,
+            runtime_version: None,
+            implementor: None,
+            os_arch: None,
+            installed_at,
+        }
+    }
+
+    pub fn path(install_path: &Path) -> PathBuf {
+        install_path.join(MANIFEST_FILE)
+    }
+
+    // Reads the manifest of an install, None when it is missing or corrupt (the install needs a reinstall)
+    pub fn read(install_path: &Path) -> Option<Self> {
+        let path = Self::path(install_path);
+        let content = fs::read_to_string(&path).ok()?;
+        let manifest = serde_json::from_str(&content).ok().and_then(|json| Self::from_json(&json));
+        if manifest.is_none() {
+            warn!("Ignoring corrupt manifest at {}", path.display());
+        }
+        manifest
+    }
+
+    pub fn write(&self, install_path: &Path) -> std::io::Result<()> {
+        let content = serde_json::to_string_pretty(&self.to_json())?;
+        write_atomically(&Self::path(install_path), &content)
+    }
+
+    fn to_json(&self) -> serde_json::Value {
+        serde_json::json!({
+            "java_version": self.java_version,
+            "release_name": self.release_name,
+            "asset_name": self.asset_name,
+            "checksum": self.checksum,
+            "runtime_version": self.runtime_version,
+            "implementor": self.implementor,
+            "os_arch": self.os_arch,
+            "installed_at": self.installed_at,
+        })
+    }
+
+    fn from_json(json: &serde_json::Value) -> Option<Self> {
+        let optional_string = |key: &str| -> Option<Option<String>> {
+            match json.get(key) {
+                None | Some(serde_json::Value::Null) => Some(None),
+                Some(value) => value.as_str().map(|value| Some(value.to_string())),
+            }
+        };
+
+        Some(InstallManifest {
+            java_version: json.get("java_version")?.as_str()?.to_string(),
+            release_name: optional_string("release_name")?,
+            asset_name: optional_string("asset_name")?,
+            checksum: optional_string("checksum")?,
+            runtime_version: optional_string("runtime_version")?,
+            implementor: optional_string("implementor")?,
+            os_arch: optional_string("os_arch")?,
+            installed_at: json.get("installed_at")?.as_u64()?,
+        })
+    }
+}
+
+// Writes to a temporary file and renames it over path so readers never see a partial file
+pub(crate) fn write_atomically(path: &Path, content: &str) -> std::io::Result<()> {
+    let mut temp_name = path.file_name().unwrap_or_default().to_os_string();
+    temp_name.push(".tmp");
+    let temp_path = path.with_file_name(temp_name);
+
+    let mut attempt = 1;
+    loop {
+        match write_and_rename(&temp_path, path, content) {
+            Ok(()) => return Ok(()),
+            // Antivirus scanners can briefly lock freshly written files on Windows
+            Err(e) if attempt < WRITE_ATTEMPTS => {
+                warn!("Failed to write {} ({}), retrying...", path.display(), e);
+                std::thread::sleep(Duration::from_millis(100 * attempt as u64));
+                attempt += 1;
+            }
+            Err(e) => {
+                let _ = fs::remove_file(&temp_path);
+                return Err(e);
+            }
+        }
+    }
+}
+
+fn write_and_rename(temp_path: &Path, path: &Path, content: &str) -> std::io::Result<()> {
+    let mut file = fs::File::create(temp_path)?;
+    std::io::Write::write_all(&mut file, content.as_bytes())?;
+    file.sync_all()?;
+    drop(file);
+    fs::rename(temp_path, path)
+}
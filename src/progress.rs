@@ -0,0 +1,182 @@
+use std::collections::VecDeque;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use tokio::sync::mpsc::Sender;
+use tokio::sync::mpsc::error::TrySendError;
+
+// Steps of a setup run, in the order they happen
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Stage {
+    // Fetching the release metadata
+    Resolve,
+    Download,
+    Extract,
+    // Copying or linking the JDK into install_path
+    Install,
+    // java -version in the install and the optional HelloWorld toolchain check
+    Verify,
+    // PATH, the launcher shim and JAVA_HOME validation
+    Configure,
+    // Removing the temporary files
+    Cleanup,
+}
+
+// Receives progress from every stage of a setup. All methods default to doing nothing, so
+// implementations only override what they display. A failing stage is never finished.
+pub trait ProgressReporter: Send + Sync {
+    fn stage_started(&self, stage: Stage) {
+        let _ = stage;
+    }
+
+    // total is the Content-Length, None when the server doesn't send one
+    fn bytes_progress(&self, downloaded: u64, total: Option<u64>) {
+        let _ = (downloaded, total);
+    }
+
+    // total is only known for zip archives, tar.gz entries are counted as they are read
+    fn entry_progress(&self, extracted: u64, total: Option<u64>) {
+        let _ = (extracted, total);
+    }
+
+    // path is relative to the install root
+    fn file_copied(&self, path: &Path) {
+        let _ = path;
+    }
+
+    fn stage_finished(&self, stage: Stage) {
+        let _ = stage;
+    }
+}
+
+// The default reporter, setup output only goes to the log
+#[derive(Debug, Clone, Copy, Default)]
+pub struct NoopReporter;
+
+impl ProgressReporter for NoopReporter {}
+
+// Adapts a plain download callback, see JavaSetup::set_progress_callback
+pub(crate) struct BytesCallback<F>(pub(crate) Mutex<F>);
+
+impl<F: FnMut(u64, Option<u64>) + Send> ProgressReporter for BytesCallback<F> {
+    fn bytes_progress(&self, downloaded: u64, total: Option<u64>) {
+        (self.0.lock().unwrap_or_else(|poisoned| poisoned.into_inner()))(downloaded, total);
+    }
+}
+
+// What a ChannelReporter sends, one event per ProgressReporter call plus the outcome of the setup
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum InstallEvent {
+    StageStarted(Stage),
+    BytesProgress { downloaded: u64, total: Option<u64> },
+    EntryProgress { extracted: u64, total: Option<u64> },
+    FileCopied(PathBuf),
+    StageFinished(Stage),
+    // Sent last, install_path is where the JDK ended up
+    Completed { install_path: PathBuf },
+    // Sent last, with the error's message
+    Failed(String),
+}
+
+impl InstallEvent {
+    // Progress events are superseded by the next one, so they can be dropped when the UI lags
+    fn is_droppable(&self) -> bool {
+        matches!(
+            self,
+            InstallEvent::BytesProgress { .. } | InstallEvent::EntryProgress { .. } | InstallEvent::FileCopied(_)
+        )
+    }
+}
+
+// Forwards progress to an mpsc channel, see JavaSetup::set_event_sender. The reporter is called
+// from blocking extraction code, so it never waits for the receiver: while the channel is full,
+// progress events are dropped and stage events are queued in order, to be sent as soon as there
+// is room again and at the latest by flush.
+pub struct ChannelReporter {
+    pub(crate) sender: Sender<InstallEvent>,
+    pending: Mutex<VecDeque<InstallEvent>>,
+    // Also receives every call, NoopReporter when the channel is the only output
+    pub(crate) inner: Arc<dyn ProgressReporter>,
+}
+
+impl ChannelReporter {
+    pub fn new(sender: Sender<InstallEvent>, inner: Arc<dyn ProgressReporter>) -> Self {
+        ChannelReporter {
+            sender,
+            pending: Mutex::new(VecDeque::new()),
+            inner,
+        }
+    }
+
+    fn send(&self, event: InstallEvent) {
+        let mut pending = self.pending.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        // Sends what is queued first so the receiver sees the events in order
+        while let Some(queued) = pending.pop_front() {
+            match self.sender.try_send(queued) {
+                Ok(()) => {}
+                Err(TrySendError::Full(queued)) => {
+                    pending.push_front(queued);
+                    break;
+                }
+                // Nobody listens anymore
+                Err(TrySendError::Closed(_)) => pending.clear(),
+            }
+        }
+        if !pending.is_empty() {
+            if !event.is_droppable() {
+                pending.push_back(event);
+            }
+            return;
+        }
+        if let Err(TrySendError::Full(event)) = self.sender.try_send(event)
+            && !event.is_droppable()
+        {
+            pending.push_back(event);
+        }
+    }
+
+    // Waits until every queued event is in the channel
+    pub async fn flush(&self) {
+        loop {
+            let Some(event) = self.pending.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).pop_front() else {
+                return;
+            };
+            if self.sender.send(event).await.is_err() {
+                return;
+            }
+        }
+    }
+
+    // Sends the final event after flushing, waiting for room in the channel
+    pub async fn finish(&self, event: InstallEvent) {
+        self.flush().await;
+        let _ = self.sender.send(event).await;
+    }
+}
+
+impl ProgressReporter for ChannelReporter {
+    fn stage_started(&self, stage: Stage) {
+        self.inner.stage_started(stage);
+        self.send(InstallEvent::StageStarted(stage));
+    }
+
+    fn bytes_progress(&self, downloaded: u64, total: Option<u64>) {
+        self.inner.bytes_progress(downloaded, total);
+        self.send(InstallEvent::BytesProgress { downloaded, total });
+    }
+
+    fn entry_progress(&self, extracted: u64, total: Option<u64>) {
+        self.inner.entry_progress(extracted, total);
+        self.send(InstallEvent::EntryProgress { extracted, total });
+    }
+
+    fn file_copied(&self, path: &Path) {
+        self.inner.file_copied(path);
+        self.send(InstallEvent::FileCopied(path.to_path_buf()));
+    }
+
+    fn stage_finished(&self, stage: Stage) {
+        self.inner.stage_finished(stage);
+        self.send(InstallEvent::StageFinished(stage));
+    }
+}
@@ -0,0 +1,90 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::manifest::InstallManifest;
+use crate::verify_installation;
+
+// A JDK found on the system together with the version reported by java -version
+#[derive(Debug, Clone)]
+pub struct DiscoveredJdk {
+    pub path: PathBuf,
+    pub version: String,
+    // Present when the JDK was installed by this crate
+    pub manifest: Option<InstallManifest>,
+}
+
+// Finds Java installations in the common install locations of each platform
+pub struct JdkRegistry;
+
+impl JdkRegistry {
+    // Scans the standard locations and JAVA_HOME
+    pub fn discover() -> Vec<DiscoveredJdk> {
+        Self::discover_in(&[])
+    }
+
+    // Same as discover, also looking inside the given roots (e.g. this crate's install root)
+    pub fn discover_in(extra_roots: &[&Path]) -> Vec<DiscoveredJdk> {
+        let mut candidates: Vec<PathBuf> = Vec::new();
+
+        for root in extra_roots {
+            candidates.push(root.to_path_buf());
+            candidates.extend(child_dirs(root));
+        }
+
+        if cfg!(windows) {
+            // Vendors install under C:\Program Files\<Vendor>\jdk-*
+            let program_files = std::env::var("ProgramFiles").unwrap_or_else(|_| "C:\\Program Files".into());
+            for vendor_dir in child_dirs(Path::new(&program_files)) {
+                candidates.extend(child_dirs(&vendor_dir).into_iter().filter(|dir| {
+                    dir.file_name()
+                        .map(|name| name.to_string_lossy().to_lowercase().starts_with("jdk"))
+                        .unwrap_or(false)
+                }));
+            }
+        } else if cfg!(target_os = "macos") {
+            for bundle in child_dirs(Path::new("/Library/Java/JavaVirtualMachines")) {
+                candidates.push(bundle.join("Contents").join("Home"));
+            }
+        } else {
+            candidates.extend(child_dirs(Path::new("/usr/lib/jvm")));
+        }
+
+        if let Ok(java_home) = std::env::var("JAVA_HOME") {
+            candidates.push(PathBuf::from(java_home));
+        }
+
+        // Symlinked aliases (e.g. /usr/lib/jvm/default-java) are reported once
+        let mut seen: Vec<PathBuf> = Vec::new();
+        let mut discovered = Vec::new();
+        for candidate in candidates {
+            if !candidate.join("bin").is_dir() {
+                continue;
+            }
+            let canonical = fs::canonicalize(&candidate).unwrap_or_else(|_| candidate.clone());
+            if seen.contains(&canonical) {
+                continue;
+            }
+            seen.push(canonical);
+
+            if let Ok(version) = verify_installation(&candidate) {
+                let manifest = InstallManifest::read(&candidate);
+                discovered.push(DiscoveredJdk { path: candidate, version, manifest });
+            }
+        }
+        discovered
+    }
+}
+
+// Lists the direct subdirectories of dir, empty if it can't be read
+fn child_dirs(dir: &Path) -> Vec<PathBuf> {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    let mut dirs: Vec<PathBuf> = entries
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| path.is_dir())
+        .collect();
+    dirs.sort();
+    dirs
+}
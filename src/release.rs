@@ -0,0 +1,83 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+use crate::version::JavaVersion;
+
+// The key=value `release` file every JDK ships at its root
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct JdkRelease {
+    // JAVA_VERSION, e.g. 17.0.9
+    pub java_version: Option<String>,
+    // JAVA_RUNTIME_VERSION, e.g. 17.0.9+9
+    pub runtime_version: Option<String>,
+    // IMPLEMENTOR, e.g. Eclipse Adoptium
+    pub implementor: Option<String>,
+    // OS_ARCH as the JDK build names it (x86_64, amd64, aarch64, ...)
+    pub os_arch: Option<String>,
+    pub os_name: Option<String>,
+    // Every key of the file, values unquoted
+    pub properties: BTreeMap<String, String>,
+}
+
+impl JdkRelease {
+    pub fn parse(content: &str) -> Self {
+        let properties: BTreeMap<String, String> = content
+            .lines()
+            .filter(|line| !line.trim_start().starts_with('#'))
+            .filter_map(|line| line.split_once('='))
+            .map(|(key, value)| {
+                let value = value.trim();
+                let value = value.strip_prefix('"').and_then(|value| value.strip_suffix('"')).unwrap_or(value);
+                (key.trim().to_string(), value.to_string())
+            })
+            .collect();
+
+        let get = |key: &str| properties.get(key).filter(|value| !value.is_empty()).cloned();
+        JdkRelease {
+            java_version: get("JAVA_VERSION"),
+            runtime_version: get("JAVA_RUNTIME_VERSION"),
+            implementor: get("IMPLEMENTOR"),
+            os_arch: get("OS_ARCH"),
+            os_name: get("OS_NAME"),
+            properties,
+        }
+    }
+
+    // Reads the release file at path, None when the install has none
+    pub fn read(path: &Path) -> std::io::Result<Option<Self>> {
+        match fs::read_to_string(path) {
+            Ok(content) => Ok(Some(Self::parse(&content))),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    pub fn version(&self) -> Option<JavaVersion> {
+        self.runtime_version
+            .as_deref()
+            .or(self.java_version.as_deref())
+            .and_then(JavaVersion::parse)
+    }
+
+    // Whether OS_ARCH names the same architecture as arch (a std::env::consts::ARCH or Adoptium
+    // name), true when the file doesn't say
+    pub fn matches_arch(&self, arch: &str) -> bool {
+        match &self.os_arch {
+            Some(os_arch) => normalize_arch(os_arch) == normalize_arch(arch),
+            None => true,
+        }
+    }
+}
+
+// Maps the spellings used by JDK builds to the Rust names
+fn normalize_arch(arch: &str) -> String {
+    match arch.to_ascii_lowercase().as_str() {
+        "amd64" | "x64" | "x86-64" => "x86_64".to_string(),
+        "arm64" => "aarch64".to_string(),
+        "i386" | "i586" | "i686" => "x86".to_string(),
+        "ppc64" | "ppc64le" => "powerpc64".to_string(),
+        "sparcv9" => "sparc64".to_string(),
+        other => other.to_string(),
+    }
+}
@@ -0,0 +1,157 @@
+// Persists PATH and JAVA_HOME changes on Linux and macOS through export lines in the user's shell profiles
+use std::fs;
+use std::path::{Path, PathBuf};
+
+// Ends every line written here, so they can be found again without touching the user's own lines
+const MARKER: &str = "# added by launcher-java-installer";
+
+// ~/.profile is read by login shells, ~/.zshrc by zsh (the default shell on macOS) which skips it
+pub fn profile_paths() -> Vec<PathBuf> {
+    let Some(home) = std::env::var_os("HOME").map(PathBuf::from) else {
+        return Vec::new();
+    };
+    let mut profiles = vec![home.join(".profile")];
+    let zshrc = home.join(".zshrc");
+    let uses_zsh = std::env::var("SHELL").is_ok_and(|shell| shell.ends_with("zsh"));
+    if uses_zsh || zshrc.exists() {
+        profiles.push(zshrc);
+    }
+    profiles
+}
+
+// Escapes a value for a double-quoted shell string
+fn escape(value: &str) -> String {
+    value
+        .chars()
+        .flat_map(|c| match c {
+            '"' | '\\' | '$' | '`' => vec!['\\', c],
+            c => vec![c],
+        })
+        .collect()
+}
+
+fn export_line(bin_dir: &str) -> String {
+    // Double-quoted so $PATH expands, the directory itself is escaped
+    format!("export PATH=\"{}:$PATH\" {}", escape(bin_dir), MARKER)
+}
+
+fn var_line(name: &str, value: &str) -> String {
+    format!("export {}=\"{}\" {}", name, escape(value), MARKER)
+}
+
+// Directories exported by our lines in a profile, in file order
+pub fn exported_dirs(profile: &Path) -> std::io::Result<Vec<String>> {
+    let content = match fs::read_to_string(profile) {
+        Ok(content) => content,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e),
+    };
+    Ok(content
+        .lines()
+        .filter_map(|line| line.strip_suffix(MARKER))
+        .filter_map(|line| line.trim_end().strip_prefix("export PATH=\""))
+        .filter_map(|line| line.strip_suffix(":$PATH\""))
+        .map(unescape)
+        .collect())
+}
+
+fn unescape(value: &str) -> String {
+    let mut unescaped = String::with_capacity(value.len());
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => unescaped.extend(chars.next()),
+            c => unescaped.push(c),
+        }
+    }
+    unescaped
+}
+
+// Appends the export line for bin_dir unless the profile has it. With move_to_end an existing line
+// is moved to the end of the file instead, where it runs last and so comes first in PATH.
+// Returns whether the profile changed.
+pub fn add_entry(profile: &Path, bin_dir: &str, move_to_end: bool) -> std::io::Result<bool> {
+    let line = export_line(bin_dir);
+    let content = match fs::read_to_string(profile) {
+        Ok(content) => content,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => String::new(),
+        Err(e) => return Err(e),
+    };
+    let lines: Vec<&str> = content.lines().collect();
+    if lines.last() == Some(&line.as_str()) || (!move_to_end && lines.contains(&line.as_str())) {
+        return Ok(false);
+    }
+
+    let mut lines: Vec<&str> = lines.into_iter().filter(|existing| *existing != line).collect();
+    lines.push(&line);
+    write(profile, &join_lines(&lines, ends_with_newline(&content)))?;
+    Ok(true)
+}
+
+// Sets name to value through a single export line, replacing the one written by an earlier run.
+// Returns whether the profile changed
+pub fn set_var(profile: &Path, name: &str, value: &str) -> std::io::Result<bool> {
+    let line = var_line(name, value);
+    let prefix = format!("export {}=", name);
+    let content = match fs::read_to_string(profile) {
+        Ok(content) => content,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => String::new(),
+        Err(e) => return Err(e),
+    };
+    let ours = |existing: &str| existing.starts_with(&prefix) && existing.ends_with(MARKER);
+    let lines: Vec<&str> = content.lines().collect();
+    let previous: Vec<&str> = lines.iter().copied().filter(|existing| ours(existing)).collect();
+    if previous == [line.as_str()] {
+        return Ok(false);
+    }
+
+    let mut lines: Vec<&str> = lines.into_iter().filter(|existing| !ours(existing)).collect();
+    lines.push(&line);
+    write(profile, &join_lines(&lines, ends_with_newline(&content)))?;
+    Ok(true)
+}
+
+// Removes the export line for bin_dir. Returns whether the profile changed
+pub fn remove_entry(profile: &Path, bin_dir: &str) -> std::io::Result<bool> {
+    let line = export_line(bin_dir);
+    remove_lines(profile, |existing| existing == line)
+}
+
+// Removes our export line for name if it sets value. Returns whether the profile changed
+pub fn remove_var(profile: &Path, name: &str, value: &str) -> std::io::Result<bool> {
+    let line = var_line(name, value);
+    remove_lines(profile, |existing| existing == line)
+}
+
+fn remove_lines(profile: &Path, remove: impl Fn(&str) -> bool) -> std::io::Result<bool> {
+    let content = match fs::read_to_string(profile) {
+        Ok(content) => content,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(false),
+        Err(e) => return Err(e),
+    };
+    if !content.lines().any(&remove) {
+        return Ok(false);
+    }
+    let lines: Vec<&str> = content.lines().filter(|existing| !remove(existing)).collect();
+    write(profile, &join_lines(&lines, ends_with_newline(&content)))?;
+    Ok(true)
+}
+
+// A profile whose last line has no newline keeps it that way, so removing our lines restores it exactly
+fn ends_with_newline(content: &str) -> bool {
+    content.is_empty() || content.ends_with('\n')
+}
+
+fn join_lines(lines: &[&str], trailing_newline: bool) -> String {
+    let mut content = lines.join("\n");
+    if trailing_newline && !content.is_empty() {
+        content.push('\n');
+    }
+    content
+}
+
+fn write(profile: &Path, content: &str) -> std::io::Result<()> {
+    // Dotfile managers often symlink profiles, the rename must replace the target and not the link
+    let profile = fs::canonicalize(profile).unwrap_or_else(|_| profile.to_path_buf());
+    crate::manifest::write_atomically(&profile, content)
+}
@@ -0,0 +1,23 @@
+use std::future::Future;
+use std::path::Path;
+use std::pin::Pin;
+
+use crate::progress::ProgressReporter;
+use crate::ReleaseAsset;
+
+// Future returned by JdkSource methods, boxed so sources can be used as trait objects
+pub type SourceFuture<'a, T> = Pin<Box<dyn Future<Output = Result<T, Box<dyn std::error::Error + Send + Sync>>> + Send + 'a>>;
+
+// Where the JDK archive comes from, replacing the Adoptium API and the HTTP download. A source
+// only has to produce the archive file, extraction, install and environment setup stay the same.
+// ReleaseAsset::link is passed back untouched, so it can be any URI the source understands
+// (magnet:, an internal mirror scheme, a file share path, ...).
+pub trait JdkSource: Send + Sync {
+    // The package to install for java_version, None when the source has none
+    fn resolve<'a>(&'a self, java_version: &'a str) -> SourceFuture<'a, Option<ReleaseAsset>>;
+
+    // Writes the archive of asset to path. The extractor picks the format from path's extension,
+    // taken from ReleaseAsset::name when download_path is a directory. The checksum policy is
+    // applied to the file afterwards, using ReleaseAsset::checksum.
+    fn fetch<'a>(&'a self, asset: &'a ReleaseAsset, path: &'a Path, progress: &'a dyn ProgressReporter) -> SourceFuture<'a, ()>;
+}
@@ -0,0 +1,50 @@
+use std::io::Read;
+use std::path::Path;
+
+use bytes::{Buf, Bytes};
+use tokio::sync::mpsc::Receiver;
+
+use crate::archive::{unpack_tar_gz, DuplicateEntryPolicy};
+use crate::progress::ProgressReporter;
+
+// Chunks of the response body the download task can get ahead of the unpacker
+pub(crate) const STREAM_BUFFER_CHUNKS: usize = 16;
+
+// Blocking reader over the chunks sent by the download task, ends when the sender is dropped
+pub(crate) struct ChannelReader {
+    chunks: Receiver<Bytes>,
+    current: Bytes,
+}
+
+impl ChannelReader {
+    pub(crate) fn new(chunks: Receiver<Bytes>) -> Self {
+        ChannelReader { chunks, current: Bytes::new() }
+    }
+}
+
+impl Read for ChannelReader {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        while !self.current.has_remaining() {
+            match self.chunks.blocking_recv() {
+                Some(chunk) => self.current = chunk,
+                None => return Ok(0),
+            }
+        }
+        let len = buf.len().min(self.current.remaining());
+        self.current.copy_to_slice(&mut buf[..len]);
+        Ok(len)
+    }
+}
+
+// Unpacks a tar.gz stream into destination, must run on a blocking thread
+pub(crate) fn unpack_stream(
+    mut reader: ChannelReader,
+    destination: &Path,
+    duplicates: DuplicateEntryPolicy,
+    progress: &dyn ProgressReporter,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    unpack_tar_gz(&mut reader, destination, duplicates, progress)?;
+    // Reads the padding after the tar end marker so the download side hashes the whole body
+    std::io::copy(&mut reader, &mut std::io::sink())?;
+    Ok(())
+}
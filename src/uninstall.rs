@@ -0,0 +1,99 @@
+use std::path::{Path, PathBuf};
+
+use walkdir::WalkDir;
+
+use crate::manifest::InstallManifest;
+use crate::same_path_entry;
+
+// What removing an install would delete and which PATH entries it would strip
+#[derive(Debug, Clone, Default)]
+pub struct UninstallPreview {
+    pub install_path: PathBuf,
+    // Files and directories under install_path in removal order (contents first), install_path last
+    pub entries: Vec<PathBuf>,
+    pub total_bytes: u64,
+    // Whether install_path has this crate's manifest, installs without one are never removed
+    pub managed: bool,
+    // Entries of the user's PATH pointing at the install's bin
+    pub path_entries: Vec<String>,
+    // The user's PATH once those entries are stripped
+    pub new_user_path: Option<String>,
+}
+
+impl UninstallPreview {
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty() && self.path_entries.is_empty()
+    }
+}
+
+// Lists what uninstalling install_path would do, without touching anything
+pub(crate) fn preview(install_path: &Path, jdk_bin_path: &str, user_path: &str) -> std::io::Result<UninstallPreview> {
+    let mut preview = UninstallPreview {
+        install_path: install_path.to_path_buf(),
+        managed: InstallManifest::path(install_path).is_file(),
+        ..UninstallPreview::default()
+    };
+
+    match install_path.symlink_metadata() {
+        // In Link mode install_path is the link, uninstall removes it and leaves the target alone
+        Ok(metadata) if metadata.is_symlink() => preview.entries.push(install_path.to_path_buf()),
+        Ok(_) => list_entries(install_path, &mut preview)?,
+        Err(_) => {}
+    }
+
+    let (stripped, kept): (Vec<String>, Vec<String>) = std::env::split_paths(user_path)
+        .map(|entry| entry.to_string_lossy().into_owned())
+        .filter(|entry| !entry.is_empty())
+        .partition(|entry| same_path_entry(entry, jdk_bin_path));
+    if !stripped.is_empty() {
+        preview.new_user_path = Some(kept.join(crate::PATH_SEPARATOR));
+    }
+    preview.path_entries = stripped;
+    Ok(preview)
+}
+
+// Adds the files and directories under install_path in removal order, symlinks are never followed
+fn list_entries(install_path: &Path, preview: &mut UninstallPreview) -> std::io::Result<()> {
+    for entry in WalkDir::new(install_path).contents_first(true) {
+        let entry = entry?;
+        let metadata = entry.path().symlink_metadata()?;
+        if metadata.is_file() {
+            preview.total_bytes += metadata.len();
+        }
+        preview.entries.push(entry.into_path());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{write_file, TempDir};
+
+    #[test]
+    fn preview_lists_the_install_contents_first() {
+        let dir = TempDir::new("preview-dir");
+        let install_path = dir.path().join("jdk");
+        write_file(&install_path, "bin/java", "java");
+        write_file(&install_path, "release", "JAVA_VERSION=\"17\"");
+
+        let preview = preview(&install_path, "", "").unwrap();
+        assert_eq!(preview.entries.len(), 4);
+        assert_eq!(preview.entries.last(), Some(&install_path));
+        assert_eq!(preview.total_bytes, 4 + 17);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn preview_of_a_linked_install_reports_the_link_alone() {
+        let dir = TempDir::new("preview-link");
+        let target = dir.path().join("jdk-17");
+        write_file(&target, "bin/java", "java");
+        let link = dir.path().join("current");
+        std::os::unix::fs::symlink(&target, &link).unwrap();
+
+        let preview = preview(&link, "", "").unwrap();
+        assert_eq!(preview.entries, vec![link]);
+        assert_eq!(preview.total_bytes, 0);
+    }
+}
@@ -0,0 +1,318 @@
+use crate::ReleaseAsset;
+
+// Lists the feature releases Adoptium publishes, also used as its status endpoint
+pub(crate) const ADOPTIUM_AVAILABLE_RELEASES_URL: &str = "https://api.adoptium.net/v3/info/available_releases";
+
+// Feature releases with GA builds, oldest first
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct AvailableVersions {
+    pub all: Vec<u32>,
+    // The long-term support releases among them
+    pub lts: Vec<u32>,
+}
+
+impl AvailableVersions {
+    // Reads Adoptium's available_releases answer, failing when either list is missing
+    pub(crate) fn from_adoptium(json: &serde_json::Value) -> Result<Self, serde_json::Error> {
+        let versions = |key: &str| serde_json::from_value::<Vec<u32>>(json.get(key).cloned().unwrap_or_default());
+        Ok(AvailableVersions {
+            all: versions("available_releases")?,
+            lts: versions("available_lts_releases")?,
+        })
+    }
+
+    // The newest LTS release, the usual pick for a launcher without a required version
+    pub fn latest_lts(&self) -> Option<u32> {
+        self.lts.iter().copied().max()
+    }
+}
+
+// JDK distributions whose release APIs packages can be resolved from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Vendor {
+    // Eclipse Temurin and the other builds listed by api.adoptium.net
+    #[default]
+    Adoptium,
+    // Amazon Corretto, listed by the foojay Disco API since Corretto has no metadata API of its own
+    Corretto,
+    // Azul Zulu, from the Azul metadata API
+    Zulu,
+    // GraalVM Community, from the graalvm-ce-builds GitHub releases. Only ships JDK images
+    GraalVm,
+}
+
+impl Vendor {
+    pub fn api(&self) -> &'static dyn VendorApi {
+        match self {
+            Vendor::Adoptium => &Adoptium,
+            Vendor::Corretto => &Corretto,
+            Vendor::Zulu => &Zulu,
+            Vendor::GraalVm => &GraalVm,
+        }
+    }
+}
+
+// What a setup asks a vendor for, in Adoptium's spelling: os is linux, windows or mac,
+// architecture x64, aarch64, ..., image_type jdk or jre
+#[derive(Debug, Clone, Copy)]
+pub struct PackageQuery<'a> {
+    // Feature version, e.g. 17
+    pub java_version: &'a str,
+    pub image_type: &'a str,
+    pub os: &'a str,
+    pub architecture: &'a str,
+}
+
+// Where a vendor publishes its releases and how to read the answer
+pub trait VendorApi: Send + Sync {
+    // Small endpoint used to check that the API answers
+    fn status_url(&self) -> String;
+
+    // URL of the metadata listing the GA packages matching query
+    fn metadata_url(&self, query: &PackageQuery) -> String;
+
+    // Every package of a metadata response that has a download link, the one to install first
+    fn packages(&self, json: &serde_json::Value, query: &PackageQuery) -> Vec<ReleaseAsset>;
+
+    // URL of the metadata listing the query's packages for every architecture, used to explain an
+    // empty answer. None skips that lookup
+    fn architectures_url(&self, _query: &PackageQuery) -> Option<String> {
+        None
+    }
+
+    // Every architecture with a package in an architectures_url response, sorted
+    fn architectures(&self, _json: &serde_json::Value) -> Vec<String> {
+        Vec::new()
+    }
+}
+
+fn str_field(value: &serde_json::Value, key: &str) -> Option<String> {
+    value.get(key).and_then(|field| field.as_str()).map(|field| field.to_string())
+}
+
+fn file_name_of(link: &str) -> String {
+    link.rsplit('/').next().unwrap_or(link).to_string()
+}
+
+// The archive format each vendor ships for an OS
+fn archive_type(os: &str) -> &'static str {
+    if os == "windows" { "zip" } else { "tar.gz" }
+}
+
+// Most APIs other than Adoptium spell macOS out
+fn long_os_name(os: &str) -> &str {
+    if os == "mac" { "macos" } else { os }
+}
+
+struct Adoptium;
+
+impl VendorApi for Adoptium {
+    fn status_url(&self) -> String {
+        ADOPTIUM_AVAILABLE_RELEASES_URL.to_string()
+    }
+
+    fn metadata_url(&self, query: &PackageQuery) -> String {
+        format!(
+            "https://api.adoptium.net/v3/assets/feature_releases/{}/ga?architecture={}&os={}&image_type={}",
+            query.java_version, query.architecture, query.os, query.image_type
+        )
+    }
+
+    // A JSON array of releases, each with binaries[].package
+    fn packages(&self, json: &serde_json::Value, _query: &PackageQuery) -> Vec<ReleaseAsset> {
+        json.as_array()
+            .into_iter()
+            .flatten()
+            .flat_map(|release| {
+                release.get("binaries")
+                    .and_then(|binaries| binaries.as_array())
+                    .into_iter()
+                    .flatten()
+                    .filter_map(move |binary| adoptium_asset(release, binary))
+            })
+            .collect()
+    }
+
+    fn architectures_url(&self, query: &PackageQuery) -> Option<String> {
+        Some(format!(
+            "https://api.adoptium.net/v3/assets/feature_releases/{}/ga?os={}&image_type={}",
+            query.java_version, query.os, query.image_type
+        ))
+    }
+
+    fn architectures(&self, json: &serde_json::Value) -> Vec<String> {
+        let mut architectures: Vec<String> = json.as_array()
+            .into_iter()
+            .flatten()
+            .filter_map(|release| release.get("binaries").and_then(|binaries| binaries.as_array()))
+            .flatten()
+            .filter_map(|binary| binary.get("architecture").and_then(|architecture| architecture.as_str()))
+            .map(|architecture| architecture.to_string())
+            .collect();
+        architectures.sort();
+        architectures.dedup();
+        architectures
+    }
+}
+
+fn adoptium_asset(release: &serde_json::Value, binary: &serde_json::Value) -> Option<ReleaseAsset> {
+    let package = binary.get("package")?;
+    let link = package.get("link").and_then(|link| link.as_str())?;
+
+    Some(ReleaseAsset {
+        release_name: str_field(release, "release_name"),
+        vendor: str_field(release, "vendor"),
+        name: str_field(package, "name").unwrap_or_else(|| file_name_of(link)),
+        link: link.to_string(),
+        size: package.get("size").and_then(|size| size.as_u64()),
+        checksum: str_field(package, "checksum"),
+    })
+}
+
+struct Corretto;
+
+impl VendorApi for Corretto {
+    fn status_url(&self) -> String {
+        "https://api.foojay.io/disco/v3.0/distributions/corretto".to_string()
+    }
+
+    fn metadata_url(&self, query: &PackageQuery) -> String {
+        format!(
+            "https://api.foojay.io/disco/v3.0/packages?distribution=corretto&version={}&os={}&architecture={}&archive_type={}&package_type={}&release_status=ga&latest=available",
+            query.java_version,
+            long_os_name(query.os),
+            query.architecture,
+            archive_type(query.os),
+            query.image_type
+        )
+    }
+
+    // An object whose result array lists one entry per package, the link under links
+    fn packages(&self, json: &serde_json::Value, _query: &PackageQuery) -> Vec<ReleaseAsset> {
+        json.get("result")
+            .and_then(|result| result.as_array())
+            .into_iter()
+            .flatten()
+            .filter_map(|package| {
+                let link = package.get("links")
+                    .and_then(|links| links.get("pkg_download_redirect"))
+                    .and_then(|link| link.as_str())
+                    .map(|link| link.to_string())
+                    .or_else(|| str_field(package, "direct_download_uri"))?;
+                Some(ReleaseAsset {
+                    release_name: str_field(package, "java_version"),
+                    vendor: Some("amazon".to_string()),
+                    name: str_field(package, "filename").unwrap_or_else(|| file_name_of(&link)),
+                    link,
+                    size: package.get("size").and_then(|size| size.as_u64()),
+                    checksum: str_field(package, "checksum").filter(|_| {
+                        package.get("checksum_type").and_then(|checksum_type| checksum_type.as_str()) == Some("sha256")
+                    }),
+                })
+            })
+            .collect()
+    }
+}
+
+struct Zulu;
+
+impl VendorApi for Zulu {
+    fn status_url(&self) -> String {
+        "https://api.azul.com/metadata/v1/zulu/packages/?java_version=17&latest=true&page_size=1".to_string()
+    }
+
+    fn metadata_url(&self, query: &PackageQuery) -> String {
+        format!(
+            "https://api.azul.com/metadata/v1/zulu/packages/?java_version={}&os={}&arch={}&archive_type={}&java_package_type={}&javafx_bundled=false&release_status=ga&availability_types=CA&latest=true&include_fields=sha256_hash,size",
+            query.java_version,
+            long_os_name(query.os),
+            query.architecture,
+            archive_type(query.os),
+            query.image_type
+        )
+    }
+
+    // A JSON array of packages with download_url and java_version as a list of numbers
+    fn packages(&self, json: &serde_json::Value, _query: &PackageQuery) -> Vec<ReleaseAsset> {
+        json.as_array()
+            .into_iter()
+            .flatten()
+            .filter_map(|package| {
+                let link = str_field(package, "download_url")?;
+                let release_name = package.get("java_version")
+                    .and_then(|version| version.as_array())
+                    .map(|numbers| {
+                        numbers.iter()
+                            .filter_map(|number| number.as_u64())
+                            .map(|number| number.to_string())
+                            .collect::<Vec<_>>()
+                            .join(".")
+                    });
+                Some(ReleaseAsset {
+                    release_name,
+                    vendor: Some("azul".to_string()),
+                    name: str_field(package, "name").unwrap_or_else(|| file_name_of(&link)),
+                    link,
+                    size: package.get("size").and_then(|size| size.as_u64()),
+                    checksum: str_field(package, "sha256_hash"),
+                })
+            })
+            .collect()
+    }
+}
+
+struct GraalVm;
+
+impl VendorApi for GraalVm {
+    fn status_url(&self) -> String {
+        "https://api.github.com/repos/graalvm/graalvm-ce-builds".to_string()
+    }
+
+    // The releases API can't filter, packages picks the version and platform from the listing
+    fn metadata_url(&self, _query: &PackageQuery) -> String {
+        "https://api.github.com/repos/graalvm/graalvm-ce-builds/releases?per_page=100".to_string()
+    }
+
+    // A JSON array of GitHub releases tagged jdk-<version>, newest first, with one asset per
+    // platform, e.g. graalvm-community-jdk-21.0.1_linux-x64_bin.tar.gz
+    fn packages(&self, json: &serde_json::Value, query: &PackageQuery) -> Vec<ReleaseAsset> {
+        if query.image_type != "jdk" {
+            return Vec::new();
+        }
+        let suffix = format!("_{}-{}_bin.{}", long_os_name(query.os), query.architecture, archive_type(query.os));
+        json.as_array()
+            .into_iter()
+            .flatten()
+            .filter(|release| !release.get("prerelease").and_then(|prerelease| prerelease.as_bool()).unwrap_or(false))
+            .filter_map(|release| {
+                let tag = str_field(release, "tag_name")?;
+                let version = tag.strip_prefix("jdk-")?;
+                let feature = version.split(['.', '+']).next().unwrap_or(version);
+                (feature == query.java_version).then_some((release, tag))
+            })
+            .flat_map(|(release, tag)| {
+                let suffix = suffix.clone();
+                release.get("assets")
+                    .and_then(|assets| assets.as_array())
+                    .into_iter()
+                    .flatten()
+                    .filter_map(move |asset| {
+                        let name = str_field(asset, "name")?;
+                        if !name.starts_with("graalvm-community-jdk-") || !name.ends_with(&suffix) {
+                            return None;
+                        }
+                        Some(ReleaseAsset {
+                            release_name: Some(tag.clone()),
+                            vendor: Some("graalvm".to_string()),
+                            name,
+                            link: str_field(asset, "browser_download_url")?,
+                            size: asset.get("size").and_then(|size| size.as_u64()),
+                            // GitHub publishes the SHA-256 of release assets as "sha256:<hex>"
+                            checksum: str_field(asset, "digest")
+                                .and_then(|digest| digest.strip_prefix("sha256:").map(|hex| hex.to_string())),
+                        })
+                    })
+            })
+            .collect()
+    }
+}
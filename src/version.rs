@@ -0,0 +1,79 @@
+use std::cmp::Ordering;
+use std::fmt;
+
+// A Java version as found in release names (jdk-17.0.9+9, jdk8u392-b08) or java -version (17.0.9, 1.8.0_392)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct JavaVersion {
+    pub feature: u32,
+    pub interim: u32,
+    pub update: u32,
+    pub patch: u32,
+    // Build number, only known from release names
+    pub build: Option<u32>,
+}
+
+impl JavaVersion {
+    pub fn parse(version: &str) -> Option<Self> {
+        let version = version.trim();
+        let version = version.strip_prefix("jdk-").or_else(|| version.strip_prefix("jdk")).unwrap_or(version);
+
+        // Java 8 release names: 8u392-b08
+        if let Some((feature, rest)) = version.split_once('u')
+            && let Ok(feature) = feature.parse()
+        {
+            let (update, build) = match rest.split_once("-b") {
+                Some((update, build)) => (update, Some(leading_number(build)?)),
+                None => (rest, None),
+            };
+            return Some(JavaVersion { feature, interim: 0, update: leading_number(update)?, patch: 0, build });
+        }
+
+        let (numbers, build) = match version.split_once('+') {
+            Some((numbers, build)) => (numbers, Some(leading_number(build)?)),
+            None => (version, None),
+        };
+        // Legacy java -version output: 1.8.0_392
+        if let Some(legacy) = numbers.strip_prefix("1.") {
+            let (numbers, update) = legacy.split_once('_').unwrap_or((legacy, "0"));
+            return Some(JavaVersion {
+                feature: leading_number(numbers)?,
+                interim: 0,
+                update: leading_number(update)?,
+                patch: 0,
+                build,
+            });
+        }
+
+        let mut parts = numbers.split('.');
+        let mut next = || parts.next().map(leading_number).unwrap_or(Some(0));
+        Some(JavaVersion { feature: next()?, interim: next()?, update: next()?, patch: next()?, build })
+    }
+
+    // Compares builds only when both versions know theirs, so 17.0.9 equals jdk-17.0.9+9
+    pub fn compare(&self, other: &JavaVersion) -> Ordering {
+        let key = |version: &JavaVersion| (version.feature, version.interim, version.update, version.patch);
+        key(self).cmp(&key(other)).then_with(|| match (self.build, other.build) {
+            (Some(build), Some(other_build)) => build.cmp(&other_build),
+            _ => Ordering::Equal,
+        })
+    }
+}
+
+impl fmt::Display for JavaVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}.{}", self.feature, self.interim, self.update)?;
+        if self.patch != 0 {
+            write!(f, ".{}", self.patch)?;
+        }
+        if let Some(build) = self.build {
+            write!(f, "+{}", build)?;
+        }
+        Ok(())
+    }
+}
+
+// Parses the digits at the start of s, ignoring suffixes like "-LTS" or "-ea"
+fn leading_number(s: &str) -> Option<u32> {
+    let end = s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+    s[..end].parse().ok()
+}
@@ -0,0 +1,98 @@
+// Reads and writes persistent environment variables through the registry, without spawning PowerShell
+
+// Persistent environment scopes on Windows
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EnvScope {
+    // HKCU\Environment
+    User,
+    // HKLM\SYSTEM\CurrentControlSet\Control\Session Manager\Environment
+    Machine,
+}
+
+#[cfg(windows)]
+mod imp {
+    use super::EnvScope;
+    use windows::Win32::Foundation::{LPARAM, WPARAM};
+    use windows::Win32::UI::WindowsAndMessaging::{HWND_BROADCAST, SMTO_ABORTIFHUNG, SendMessageTimeoutW, WM_SETTINGCHANGE};
+    use winreg::RegKey;
+    use winreg::enums::{HKEY_CURRENT_USER, HKEY_LOCAL_MACHINE, KEY_READ, KEY_WRITE, RegType};
+    use winreg::types::{FromRegValue, ToRegValue};
+
+    const USER_KEY: &str = "Environment";
+    const MACHINE_KEY: &str = "SYSTEM\\CurrentControlSet\\Control\\Session Manager\\Environment";
+
+    pub fn read_var(scope: EnvScope, name: &str) -> std::io::Result<Option<String>> {
+        let key = match scope {
+            EnvScope::User => RegKey::predef(HKEY_CURRENT_USER).open_subkey_with_flags(USER_KEY, KEY_READ)?,
+            EnvScope::Machine => RegKey::predef(HKEY_LOCAL_MACHINE).open_subkey_with_flags(MACHINE_KEY, KEY_READ)?,
+        };
+        match key.get_raw_value(name) {
+            Ok(value) => Ok(Some(String::from_reg_value(&value)?)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    pub fn write_user_var(name: &str, value: &str) -> std::io::Result<()> {
+        let key = RegKey::predef(HKEY_CURRENT_USER).open_subkey_with_flags(USER_KEY, KEY_READ | KEY_WRITE)?;
+
+        // Keeps REG_SZ values as they are, anything else is written as REG_EXPAND_SZ so %VAR% entries still expand
+        let vtype = match key.get_raw_value(name) {
+            Ok(existing) if existing.vtype == RegType::REG_SZ => RegType::REG_SZ,
+            _ => RegType::REG_EXPAND_SZ,
+        };
+        let mut reg_value = value.to_reg_value();
+        reg_value.vtype = vtype;
+        key.set_raw_value(name, &reg_value)
+    }
+
+    // Removing a variable that doesn't exist succeeds
+    pub fn delete_user_var(name: &str) -> std::io::Result<()> {
+        let key = RegKey::predef(HKEY_CURRENT_USER).open_subkey_with_flags(USER_KEY, KEY_READ | KEY_WRITE)?;
+        match key.delete_value(name) {
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+            result => result,
+        }
+    }
+
+    // Tells running applications (Explorer, new shells) to reload the environment
+    pub fn broadcast_change() {
+        let param: Vec<u16> = "Environment\0".encode_utf16().collect();
+        unsafe {
+            SendMessageTimeoutW(
+                HWND_BROADCAST,
+                WM_SETTINGCHANGE,
+                WPARAM(0),
+                LPARAM(param.as_ptr() as isize),
+                SMTO_ABORTIFHUNG,
+                5000,
+                None,
+            );
+        }
+    }
+}
+
+#[cfg(not(windows))]
+mod imp {
+    use super::EnvScope;
+
+    fn unsupported() -> std::io::Error {
+        std::io::Error::new(std::io::ErrorKind::Unsupported, "the registry backend is only available on Windows")
+    }
+
+    pub fn read_var(_scope: EnvScope, _name: &str) -> std::io::Result<Option<String>> {
+        Err(unsupported())
+    }
+
+    pub fn write_user_var(_name: &str, _value: &str) -> std::io::Result<()> {
+        Err(unsupported())
+    }
+
+    pub fn delete_user_var(_name: &str) -> std::io::Result<()> {
+        Err(unsupported())
+    }
+
+    pub fn broadcast_change() {}
+}
+
+pub(crate) use imp::{broadcast_change, delete_user_var, read_var, write_user_var};